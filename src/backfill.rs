@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Write as _,
+    io::{self, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Local};
+use prometheus::proto::MetricType;
+
+use crate::{influx, metrics, sinks::archive, snapshot::Snapshot};
+
+fn load(path: &Path) -> Result<Snapshot, Box<dyn Error>> {
+    let json = serde_json::from_slice(&archive::read(path)?)?;
+    Ok(Snapshot::from_json(json)?)
+}
+
+/// Archived snapshots below `dir` taken at or after `since`, oldest first.
+pub fn snapshots(
+    dir: &Path,
+    since: Option<DateTime<Local>>,
+) -> io::Result<impl Iterator<Item = Snapshot>> {
+    let files = archive::files(dir)?;
+    Ok(files
+        .into_iter()
+        .filter(move |(time, _)| since.is_none_or(|since| *time >= since.naive_local()))
+        .filter_map(|(_, path)| match load(&path) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!("Skipping unreadable snapshot {}: {:#}", path.display(), e);
+                None
+            }
+        }))
+}
+
+/// Escapes label values and HELP texts, which are both `escaped-string`s in OpenMetrics.
+fn escape(text: &str) -> String {
+    text.replace('\\', r"\\")
+        .replace('\n', r"\n")
+        .replace('"', r#"\""#)
+}
+
+fn number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+struct Family {
+    metric_type: &'static str,
+    help: String,
+    samples: Vec<String>,
+}
+
+/// OpenMetrics text with the snapshot time on every sample, as read by
+/// `promtool tsdb create-blocks-from openmetrics`. Uses the metrics of the live exposition,
+/// with the samples of every family grouped together as OpenMetrics requires.
+pub fn openmetrics(snapshots: impl Iterator<Item = Snapshot>) -> String {
+    let mut families: BTreeMap<String, Family> = BTreeMap::new();
+    for snapshot in snapshots {
+        let timestamp = snapshot.metadata.time.timestamp_millis() as f64 / 1000.0;
+        for family in metrics::registry(&snapshot).gather() {
+            let name = family.get_name();
+            // OpenMetrics counters are named without the `_total` of their samples
+            let (family_name, metric_type) = match family.get_field_type() {
+                MetricType::COUNTER => match name.strip_suffix("_total") {
+                    Some(base) => (base, "counter"),
+                    None => (name, "unknown"),
+                },
+                MetricType::GAUGE => (name, "gauge"),
+                _ => (name, "unknown"),
+            };
+            let entry = families
+                .entry(family_name.to_string())
+                .or_insert_with(|| Family {
+                    metric_type,
+                    help: family.get_help().to_string(),
+                    samples: Vec::new(),
+                });
+            for metric in family.get_metric() {
+                let value = match family.get_field_type() {
+                    MetricType::COUNTER => metric.get_counter().get_value(),
+                    MetricType::GAUGE => metric.get_gauge().get_value(),
+                    _ => metric.get_untyped().get_value(),
+                };
+                let labels: Vec<_> = metric
+                    .get_label()
+                    .iter()
+                    .map(|l| format!("{}=\"{}\"", l.get_name(), escape(l.get_value())))
+                    .collect();
+                let labels = if labels.is_empty() {
+                    String::new()
+                } else {
+                    format!("{{{}}}", labels.join(","))
+                };
+                entry.samples.push(format!(
+                    "{}{} {} {}",
+                    name,
+                    labels,
+                    number(value),
+                    timestamp
+                ));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (name, family) in families {
+        writeln!(out, "# HELP {} {}", name, escape(&family.help)).unwrap();
+        writeln!(out, "# TYPE {} {}", name, family.metric_type).unwrap();
+        for sample in family.samples {
+            writeln!(out, "{}", sample).unwrap();
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+/// Influx line protocol with the snapshot times, written as the snapshots are read.
+pub fn influx(snapshots: impl Iterator<Item = Snapshot>, mut out: impl Write) -> io::Result<()> {
+    for snapshot in snapshots {
+        out.write_all(influx::encode(&snapshot).as_bytes())?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::openmetrics;
+    use crate::{counters::TrafficTotals, snapshot::Snapshot, usage::Traffic};
+
+    #[test]
+    fn timestamped_openmetrics() {
+        let mut first = Snapshot::example();
+        let raw = Traffic {
+            download: 100,
+            upload: 10,
+        };
+        first.traffic = Some(TrafficTotals {
+            raw,
+            total: raw,
+            restarts: 0,
+        });
+        // HELP is the label of the web interface, which may contain quotes
+        first.info.get_mut("sinr").unwrap().label = r#"SINR "signal""#.to_string();
+        // Archived snapshots are read back from their JSON
+        let first = Snapshot::from_json(first.to_json()).unwrap();
+        let mut second = first.clone();
+        second.metadata.time += Duration::minutes(1);
+        let timestamp = first.metadata.time.timestamp();
+
+        let text = openmetrics(vec![first, second].into_iter());
+        assert!(text.ends_with("# EOF\n"));
+        assert!(text.contains(&format!(
+            "# HELP huawei_metrics_download_bytes Total download traffic, continued across router counter resets\n\
+            # TYPE huawei_metrics_download_bytes counter\n\
+            huawei_metrics_download_bytes_total 100 {}\n\
+            huawei_metrics_download_bytes_total 100 {}\n",
+            timestamp,
+            timestamp + 60
+        )));
+        assert!(text.contains(&format!(
+            "# HELP huawei_metrics_sinr_db SINR \\\"signal\\\"\n# TYPE huawei_metrics_sinr_db gauge\nhuawei_metrics_sinr_db -1 {}\n",
+            timestamp
+        )));
+        assert!(text.contains(&format!(
+            "# HELP huawei_metrics_device_uptime_min Devices\n\
+            # TYPE huawei_metrics_device_uptime_min unknown\n\
+            huawei_metrics_device_uptime_min{{connection=\"offline\",friendly_name=\"AA:BB:CC:DD:EE:02\",mac=\"AA:BB:CC:DD:EE:02\"}} 0 {0}\n\
+            huawei_metrics_device_uptime_min{{connection=\"online\",friendly_name=\"Laptop\",mac=\"AA:BB:CC:DD:EE:01\"}} 90 {0}\n",
+            timestamp
+        )));
+    }
+}
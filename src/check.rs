@@ -0,0 +1,218 @@
+use std::{any::Any, fmt::Write};
+
+use crate::snapshot::Snapshot;
+
+/// Nagios plugin states, the discriminant is the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl Status {
+    fn name(&self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Critical => "CRITICAL",
+            Status::Unknown => "UNKNOWN",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        *self as i32
+    }
+
+    /// Unknown only wins over OK, a known problem is more useful than a missing value.
+    fn worst(self, other: Status) -> Status {
+        match (self, other) {
+            (Status::Unknown, Status::Ok) | (Status::Ok, Status::Unknown) => Status::Unknown,
+            (Status::Unknown, s) | (s, Status::Unknown) => s,
+            (a, b) => a.max(b),
+        }
+    }
+}
+
+/// Lower bounds for an information value, it is a problem when the value drops below them.
+pub struct Threshold {
+    pub key: &'static str,
+    pub warn: Option<f64>,
+    pub crit: Option<f64>,
+}
+
+pub struct CheckConfig {
+    pub thresholds: Vec<Threshold>,
+    /// MAC addresses of expected devices, compared case-insensitively.
+    pub known_devices: Vec<String>,
+    pub max_unknown_devices: Option<usize>,
+}
+
+fn perf_value(value: f64) -> String {
+    let mut s = format!("{:.3}", value);
+    while s.contains('.') && (s.ends_with('0') || s.ends_with('.')) {
+        s.pop();
+    }
+    s
+}
+
+/// The message of a panic, which describes what went wrong for all `expect`s.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown error")
+}
+
+/// Evaluates the thresholds, returns the status and the plugin output with perfdata.
+pub fn check(snapshot: &Snapshot, config: &CheckConfig) -> (Status, String) {
+    let mut status = Status::Ok;
+    let mut problems = Vec::new();
+    let mut summary = Vec::new();
+
+    for threshold in &config.thresholds {
+        let information = snapshot.info.get(threshold.key);
+        match information.and_then(|i| i.parsed.as_ref()) {
+            None => {
+                status = status.worst(Status::Unknown);
+                problems.push(format!("{} not available", threshold.key));
+            }
+            Some(parsed) => {
+                let text = format!(
+                    "{} {}{}",
+                    information.unwrap().label,
+                    perf_value(parsed.value),
+                    parsed.unit
+                );
+                let result = if threshold.crit.is_some_and(|c| parsed.value < c) {
+                    Status::Critical
+                } else if threshold.warn.is_some_and(|w| parsed.value < w) {
+                    Status::Warning
+                } else {
+                    Status::Ok
+                };
+                if result == Status::Ok {
+                    summary.push(text);
+                } else {
+                    problems.push(format!("{} ({})", text, result.name()));
+                }
+                status = status.worst(result);
+            }
+        }
+    }
+
+    let devices = &snapshot.devices;
+    if let Some(max) = config.max_unknown_devices {
+        let unknown: Vec<_> = devices
+            .online
+            .iter()
+            .filter(|d| {
+                !config
+                    .known_devices
+                    .iter()
+                    .any(|k| k.eq_ignore_ascii_case(&d.mac))
+            })
+            .map(|d| match &d.name {
+                Some(name) => format!("{} ({})", name, d.mac),
+                None => d.mac.clone(),
+            })
+            .collect();
+        if unknown.len() > max {
+            status = status.worst(Status::Critical);
+            problems.push(format!(
+                "{} unknown device(s): {}",
+                unknown.len(),
+                unknown.join(", ")
+            ));
+        } else {
+            summary.push(format!("{} unknown device(s)", unknown.len()));
+        }
+    }
+
+    let mut perfdata = Vec::new();
+    let mut info: Vec<_> = snapshot.info.iter().collect();
+    info.sort_by_key(|(key, _)| key.as_str());
+    for (key, information) in info {
+        let parsed = match &information.parsed {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        // Only data volumes have a standard unit of measurement
+        let (value, uom) = match parsed.bytes() {
+            Some(bytes) => (bytes as f64, "B"),
+            None => (parsed.value, ""),
+        };
+        let mut data = format!("{}={}{}", key, perf_value(value), uom);
+        if let Some(t) = config.thresholds.iter().find(|t| t.key == key) {
+            let bound =
+                |b: Option<f64>| b.map_or_else(String::new, |b| format!("{}:", perf_value(b)));
+            write!(data, ";{};{}", bound(t.warn), bound(t.crit)).unwrap();
+        }
+        perfdata.push(data);
+    }
+    for (name, _, count) in devices.counts() {
+        perfdata.push(format!("{}={}", name, count));
+    }
+
+    let mut details = problems;
+    details.extend(summary);
+    let output = format!(
+        "HUAWEI {} - {} | {}",
+        status.name(),
+        if details.is_empty() {
+            format!("{} devices online", devices.online.len())
+        } else {
+            details.join(", ")
+        },
+        perfdata.join(" ")
+    );
+    (status, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, CheckConfig, Status, Threshold};
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn thresholds() {
+        let snapshot = Snapshot::example();
+        let mut config = CheckConfig {
+            thresholds: vec![
+                Threshold {
+                    key: "sinr",
+                    warn: Some(3.0),
+                    crit: Some(-5.0),
+                },
+                Threshold {
+                    key: "rsrp",
+                    warn: None,
+                    crit: Some(-115.0),
+                },
+            ],
+            known_devices: vec!["aa:bb:cc:dd:ee:01".to_string()],
+            max_unknown_devices: Some(0),
+        };
+        let (status, output) = check(&snapshot, &config);
+        assert_eq!(status, Status::Warning);
+        assert_eq!(
+            output,
+            "HUAWEI WARNING - SINR -1dB (WARNING), RSRP -109dBm, 0 unknown device(s) | \
+            rsrp=-109;;-115: sinr=-1;3:;-5: totaldownload=2147483648B \
+            online_devices=1 offline_devices=1 total_devices=2 wifi_devices=1 wifi_2ghz_devices=0 wifi_5ghz_devices=1"
+        );
+
+        config.known_devices.clear();
+        config.thresholds.push(Threshold {
+            key: "rsrq",
+            warn: Some(-15.0),
+            crit: None,
+        });
+        let (status, output) = check(&snapshot, &config);
+        assert_eq!(status, Status::Critical);
+        assert!(output.contains("rsrq not available"));
+        assert!(output.contains("1 unknown device(s): Laptop (AA:BB:CC:DD:EE:01)"));
+    }
+}
@@ -0,0 +1,57 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::sinks::SinkEntry;
+
+/// Contents of the file passed with `--config`.
+#[derive(Debug, Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub sinks: Vec<SinkEntry>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Unable to read config {}: {:#}", path.display(), e));
+        toml::from_str(&content)
+            .unwrap_or_else(|e| panic!("Invalid config {}: {:#}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::sinks::{Format, SinkConfig};
+
+    #[test]
+    fn sink_entries() {
+        let config: Config = toml::from_str(
+            r#"
+            [[sinks]]
+            type = "stdout"
+
+            [[sinks]]
+            type = "http"
+            name = "hass"
+            url = "http://localhost:8123/api/webhook/abc"
+            format = "prometheus"
+            retry = { attempts = 3 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.sinks.len(), 2);
+        assert!(matches!(
+            &config.sinks[0].config,
+            SinkConfig::Stdout(c) if c.format == Format::Json
+        ));
+        let http = &config.sinks[1];
+        assert_eq!(http.name.as_deref(), Some("hass"));
+        assert_eq!(http.retry.attempts, 3);
+        assert_eq!(http.retry.backoff_ms, 1000);
+        assert!(matches!(&http.config, SinkConfig::Http(c) if c.method == "POST"));
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::usage::Traffic;
+
+/// Turns the router's traffic counters, which start over when the router restarts or its
+/// statistics are cleared, into totals that only ever grow.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CounterTracker {
+    /// Traffic counted by the router before its last reset.
+    offset: Traffic,
+    last_raw: Option<Traffic>,
+    last_uptime_minutes: Option<u64>,
+    restarts: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrafficTotals {
+    pub raw: Traffic,
+    pub total: Traffic,
+    pub restarts: u64,
+}
+
+impl CounterTracker {
+    /// A restart is counted when either counter or the router's operation time decreased,
+    /// only a decreasing counter moves the offset as not every restart clears the counters.
+    pub fn update(&mut self, raw: Traffic, uptime_minutes: Option<u64>) -> TrafficTotals {
+        if let Some(last) = self.last_raw {
+            let counter_decreased = raw.download < last.download || raw.upload < last.upload;
+            let uptime_decreased = matches!(
+                (self.last_uptime_minutes, uptime_minutes),
+                (Some(before), Some(now)) if now < before
+            );
+            if counter_decreased || uptime_decreased {
+                info!(
+                    "Router restarted or reset its counters (counter decreased: {}, uptime decreased: {})",
+                    counter_decreased, uptime_decreased
+                );
+                if counter_decreased {
+                    self.offset += last;
+                }
+                self.restarts += 1;
+            }
+        }
+        self.last_raw = Some(raw);
+        if uptime_minutes.is_some() {
+            self.last_uptime_minutes = uptime_minutes;
+        }
+
+        let mut total = self.offset;
+        total += raw;
+        TrafficTotals {
+            raw,
+            total,
+            restarts: self.restarts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CounterTracker;
+    use crate::usage::Traffic;
+
+    #[test]
+    fn monotonic_across_resets() {
+        let mut tracker = CounterTracker::default();
+        let mut totals = Vec::new();
+        for (download, uptime) in [(100, 10), (150, 20), (30, 2), (40, 1), (90, 60)] {
+            totals.push(tracker.update(
+                Traffic {
+                    download,
+                    upload: 0,
+                },
+                Some(uptime),
+            ));
+        }
+        let downloads: Vec<_> = totals.iter().map(|t| t.total.download).collect();
+        assert_eq!(downloads, [100, 150, 180, 190, 240]);
+        assert_eq!(totals.last().unwrap().restarts, 2);
+    }
+}
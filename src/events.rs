@@ -0,0 +1,247 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{influx::CELL_ID, snapshot::Snapshot, Device, MinuteCounter, ROUTER_UPTIME};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    DeviceJoined,
+    DeviceLeft,
+    CellChanged,
+    RouterRestarted,
+    /// Logging in or reading a page of the web interface failed.
+    ScrapeFailed,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::DeviceJoined => "device_joined",
+            EventKind::DeviceLeft => "device_left",
+            EventKind::CellChanged => "cell_changed",
+            EventKind::RouterRestarted => "router_restarted",
+            EventKind::ScrapeFailed => "scrape_failed",
+        }
+    }
+}
+
+/// Something that happened between two scrapes, shipped as a structured log line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub time: DateTime<Local>,
+    pub router: String,
+    pub kind: EventKind,
+    pub message: String,
+    /// Details like the MAC of a device, in a fixed order.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl Event {
+    fn new(snapshot: &Snapshot, kind: EventKind, message: String) -> Self {
+        Event {
+            time: snapshot.metadata.time,
+            router: snapshot.metadata.router_host.clone(),
+            kind,
+            message,
+            fields: Vec::new(),
+        }
+    }
+
+    fn field(mut self, key: &'static str, value: Option<&str>) -> Self {
+        if let Some(value) = value {
+            self.fields.push((key, value.to_string()));
+        }
+        self
+    }
+
+    pub fn scrape_failed(time: DateTime<Local>, router: &str, reason: &str) -> Self {
+        Event {
+            time,
+            router: router.to_string(),
+            kind: EventKind::ScrapeFailed,
+            message: format!("Scraping the router failed: {}", reason),
+            fields: vec![("reason", reason.to_string())],
+        }
+    }
+
+    /// The event as a JSON object with the message and all fields.
+    pub fn to_json(&self) -> Map<String, Value> {
+        let mut object = Map::new();
+        object.insert("event".to_string(), json!(self.kind.name()));
+        object.insert("router".to_string(), json!(self.router));
+        object.insert("message".to_string(), json!(self.message));
+        for (key, value) in &self.fields {
+            object.insert(key.to_string(), json!(value));
+        }
+        object
+    }
+}
+
+fn device_event(snapshot: &Snapshot, kind: EventKind, device: &Device) -> Event {
+    let name = device.name.as_deref().unwrap_or(&device.mac);
+    let verb = match kind {
+        EventKind::DeviceJoined => "came online",
+        _ => "went offline",
+    };
+    Event::new(snapshot, kind, format!("Device {} {}", name, verb))
+        .field("mac", Some(&device.mac))
+        .field("name", device.name.as_deref())
+}
+
+fn info_value<'a>(snapshot: &'a Snapshot, key: &str) -> Option<&'a str> {
+    snapshot
+        .info
+        .get(key)
+        .map(|i| i.value.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+fn uptime(snapshot: &Snapshot) -> Option<u64> {
+    let value = info_value(snapshot, ROUTER_UPTIME)?;
+    MinuteCounter::try_from_str(value, false).map(|u| u.minutes)
+}
+
+/// What events are derived from, small enough to keep in the state file between runs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Seen {
+    online: Vec<Device>,
+    cell_id: Option<String>,
+    uptime_minutes: Option<u64>,
+    restarts: Option<u64>,
+}
+
+impl Seen {
+    fn new(snapshot: &Snapshot) -> Self {
+        Seen {
+            online: snapshot.devices.online.clone(),
+            cell_id: info_value(snapshot, CELL_ID).map(str::to_owned),
+            uptime_minutes: uptime(snapshot),
+            restarts: snapshot.traffic.as_ref().map(|t| t.restarts),
+        }
+    }
+}
+
+/// Derives events by comparing every snapshot with the one before, persisted in the
+/// state file so runs from cron compare with the previous run.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Tracker {
+    previous: Option<Seen>,
+}
+
+impl Tracker {
+    /// Events since the previous snapshot, none for the first one.
+    pub fn update(&mut self, snapshot: &Snapshot) -> Vec<Event> {
+        let now = Seen::new(snapshot);
+        let previous = match self.previous.replace(now.clone()) {
+            Some(previous) => previous,
+            None => return Vec::new(),
+        };
+        let mut events = Vec::new();
+
+        let was_online = |mac: &str| previous.online.iter().any(|d| d.mac == mac);
+        for device in &now.online {
+            if !was_online(&device.mac) {
+                events.push(device_event(snapshot, EventKind::DeviceJoined, device));
+            }
+        }
+        for device in &previous.online {
+            if !now.online.iter().any(|d| d.mac == device.mac) {
+                events.push(device_event(snapshot, EventKind::DeviceLeft, device));
+            }
+        }
+
+        if let (Some(from), Some(to)) = (&previous.cell_id, &now.cell_id) {
+            if from != to {
+                events.push(
+                    Event::new(
+                        snapshot,
+                        EventKind::CellChanged,
+                        format!("Serving cell changed from {} to {}", from, to),
+                    )
+                    .field("from", Some(from))
+                    .field("to", Some(to)),
+                );
+            }
+        }
+
+        // The uptime going back is the most direct sign, counter resets need a state file
+        let uptime_reset = matches!(
+            (previous.uptime_minutes, now.uptime_minutes),
+            (Some(before), Some(now)) if now < before
+        );
+        let counter_reset = matches!(
+            (previous.restarts, now.restarts),
+            (Some(before), Some(now)) if now > before
+        );
+        if uptime_reset || counter_reset {
+            events.push(Event::new(
+                snapshot,
+                EventKind::RouterRestarted,
+                "Router restarted".to_string(),
+            ));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventKind, Tracker};
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn changes_between_snapshots() {
+        let mut first = Snapshot::example();
+        let mut cell = first.info["serialNumber"].clone();
+        cell.value = "1234567".to_string();
+        first.info.insert("cell_id".to_string(), cell);
+        let mut uptime = first.info["serialNumber"].clone();
+        uptime.value = "2 day 3 hour 4 minute".to_string();
+        first.info.insert("uptime".to_string(), uptime);
+
+        let mut tracker = Tracker::default();
+        assert!(tracker.update(&first).is_empty());
+        assert!(tracker.update(&first).is_empty());
+        // Runs from cron only see what the previous one left in the state file
+        let mut tracker: Tracker =
+            serde_json::from_str(&serde_json::to_string(&tracker).unwrap()).unwrap();
+
+        let mut second = first.clone();
+        let laptop = second.devices.online.pop().unwrap();
+        let phone = second.devices.offline.pop().unwrap();
+        second.devices.online.push(phone);
+        second.devices.offline.push(laptop);
+        second.info.get_mut("cell_id").unwrap().value = "7654321".to_string();
+        second.info.get_mut("uptime").unwrap().value = "0 day 0 hour 3 minute".to_string();
+
+        let events = tracker.update(&second);
+        let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::DeviceJoined,
+                EventKind::DeviceLeft,
+                EventKind::CellChanged,
+                EventKind::RouterRestarted
+            ]
+        );
+        assert_eq!(events[0].message, "Device AA:BB:CC:DD:EE:02 came online");
+        assert_eq!(events[1].message, "Device Laptop went offline");
+        assert_eq!(
+            events[1].fields,
+            [
+                ("mac", "AA:BB:CC:DD:EE:01".to_string()),
+                ("name", "Laptop".to_string())
+            ]
+        );
+        assert_eq!(
+            events[2].message,
+            "Serving cell changed from 1234567 to 7654321"
+        );
+        assert_eq!(
+            serde_json::Value::Object(events[3].to_json()).to_string(),
+            r#"{"event":"router_restarted","message":"Router restarted","router":"192.168.8.1"}"#
+        );
+    }
+}
@@ -0,0 +1,542 @@
+use std::{collections::BTreeMap, error::Error, fmt::Write as _, io::Write};
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use rusqlite::{params, params_from_iter, types::ValueRef, Connection, ToSql};
+
+use crate::{
+    snapshot::Snapshot,
+    usage::{first_of_month, format_bytes, Reading, Traffic, UsageLedger},
+    ConnectionType,
+};
+
+/// Schema changes in order, `PRAGMA user_version` is the number of them applied to a database.
+const MIGRATIONS: &[&str] = &[r#"
+CREATE TABLE snapshots (
+    id INTEGER PRIMARY KEY,
+    time INTEGER NOT NULL,
+    router TEXT NOT NULL,
+    download_bytes INTEGER,
+    upload_bytes INTEGER
+);
+CREATE INDEX snapshots_time ON snapshots (time);
+CREATE TABLE info (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    label TEXT NOT NULL,
+    value TEXT NOT NULL,
+    number REAL,
+    unit TEXT,
+    PRIMARY KEY (snapshot_id, key)
+);
+CREATE TABLE devices (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    mac TEXT NOT NULL,
+    name TEXT,
+    online INTEGER NOT NULL,
+    connection TEXT,
+    uptime_minutes INTEGER,
+    PRIMARY KEY (snapshot_id, mac)
+);
+"#];
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => first_of_month(date),
+        }
+    }
+
+    fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.to_string(),
+            Period::Week => start.format("Week %G-W%V").to_string(),
+            Period::Month => start.format("Month %Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Table {
+    Info,
+    Devices,
+}
+
+/// Parses ages like `90s`, `30m`, `24h`, `7d` or `2w`.
+pub fn parse_age(age: &str) -> Option<Duration> {
+    let split = age.len().checked_sub(1)?;
+    let number: i64 = age.get(..split)?.parse().ok()?;
+    match &age[split..] {
+        "s" => Some(Duration::seconds(number)),
+        "m" => Some(Duration::minutes(number)),
+        "h" => Some(Duration::hours(number)),
+        "d" => Some(Duration::days(number)),
+        "w" => Some(Duration::weeks(number)),
+        _ => None,
+    }
+}
+
+fn local_time(timestamp: i64) -> DateTime<Local> {
+    Local.timestamp_opt(timestamp, 0).unwrap()
+}
+
+/// The router's own traffic counters, resets are handled when reading them back.
+fn raw_traffic(snapshot: &Snapshot) -> (Option<u64>, Option<u64>) {
+    match &snapshot.traffic {
+        Some(totals) => (Some(totals.raw.download), Some(totals.raw.upload)),
+        None => (
+            snapshot.info.get("totaldownload").and_then(|i| i.bytes()),
+            snapshot.info.get("totalupload").and_then(|i| i.bytes()),
+        ),
+    }
+}
+
+/// An information value as stored, with the number and unit `try_parse` extracted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedInfo {
+    pub key: String,
+    pub label: String,
+    pub value: String,
+    pub number: Option<f64>,
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedDevice {
+    pub mac: String,
+    pub name: Option<String>,
+    pub online: bool,
+    pub connection: Option<String>,
+    pub uptime_minutes: Option<i64>,
+}
+
+/// A snapshot as it is stored in the history database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recorded {
+    pub time: DateTime<Local>,
+    pub router: String,
+    pub download_bytes: Option<u64>,
+    pub upload_bytes: Option<u64>,
+    pub info: Vec<RecordedInfo>,
+    pub devices: Vec<RecordedDevice>,
+}
+
+impl From<&Snapshot> for Recorded {
+    fn from(snapshot: &Snapshot) -> Self {
+        let (download_bytes, upload_bytes) = raw_traffic(snapshot);
+        let mut info: Vec<_> = snapshot
+            .info
+            .iter()
+            .map(|(key, information)| RecordedInfo {
+                key: key.clone(),
+                label: information.label.clone(),
+                value: information.value.clone(),
+                number: information.parsed.as_ref().map(|p| p.value),
+                unit: information.parsed.as_ref().map(|p| p.unit.clone()),
+            })
+            .collect();
+        info.sort_by(|a, b| a.key.cmp(&b.key));
+        let devices = snapshot
+            .devices
+            .online
+            .iter()
+            .map(|d| (d, true))
+            .chain(snapshot.devices.offline.iter().map(|d| (d, false)))
+            .map(|(dev, online)| RecordedDevice {
+                mac: dev.mac.clone(),
+                name: dev.name.clone(),
+                online,
+                connection: dev.connection.as_ref().map(ConnectionType::name),
+                uptime_minutes: dev.uptime.as_ref().map(|u| u.minutes as i64),
+            })
+            .collect();
+        Recorded {
+            time: snapshot.metadata.time,
+            router: snapshot.metadata.router_host.clone(),
+            download_bytes,
+            upload_bytes,
+            info,
+            devices,
+        }
+    }
+}
+
+/// SQLite database every snapshot is recorded in, for looking at past values without Prometheus.
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &str) -> Self {
+        let connection = Connection::open(path)
+            .unwrap_or_else(|e| panic!("Unable to open history database {}: {}", path, e));
+        Self::migrate(connection)
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Self {
+        Self::migrate(Connection::open_in_memory().unwrap())
+    }
+
+    fn migrate(mut connection: Connection) -> Self {
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .expect("Unable to read history database schema version");
+        if version > MIGRATIONS.len() {
+            panic!(
+                "History database has schema version {}, but this version only knows up to {}",
+                version,
+                MIGRATIONS.len()
+            );
+        }
+        let tx = connection.transaction().unwrap();
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            info!("Migrating history database to schema version {}", i + 1);
+            tx.execute_batch(migration)
+                .expect("Unable to migrate history database");
+        }
+        tx.pragma_update(None, "user_version", MIGRATIONS.len() as i64)
+            .unwrap();
+        tx.commit().unwrap();
+        History { connection }
+    }
+
+    pub fn record(&mut self, snapshot: &Snapshot) -> rusqlite::Result<()> {
+        let recorded = Recorded::from(snapshot);
+        let tx = self.connection.transaction()?;
+        tx.execute(
+            "INSERT INTO snapshots (time, router, download_bytes, upload_bytes) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                recorded.time.timestamp(),
+                recorded.router,
+                recorded.download_bytes.map(|b| b as i64),
+                recorded.upload_bytes.map(|b| b as i64)
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO info (snapshot_id, key, label, value, number, unit) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for i in &recorded.info {
+                insert.execute(params![id, i.key, i.label, i.value, i.number, i.unit])?;
+            }
+            let mut insert = tx.prepare(
+                "INSERT INTO devices (snapshot_id, mac, name, online, connection, uptime_minutes) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for d in &recorded.devices {
+                insert.execute(params![
+                    id,
+                    d.mac,
+                    d.name,
+                    d.online,
+                    d.connection,
+                    d.uptime_minutes
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Calls `f` with every snapshot since `since` as it was recorded, oldest first.
+    pub fn each_recorded(
+        &self,
+        since: Option<DateTime<Local>>,
+        mut f: impl FnMut(Recorded) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut snapshots = self.connection.prepare(
+            "SELECT id, time, router, download_bytes, upload_bytes FROM snapshots \
+            WHERE time >= ?1 ORDER BY time, id",
+        )?;
+        let mut info = self.connection.prepare(
+            "SELECT key, label, value, number, unit FROM info WHERE snapshot_id = ?1 ORDER BY key",
+        )?;
+        let mut devices = self.connection.prepare(
+            "SELECT mac, name, online, connection, uptime_minutes FROM devices \
+            WHERE snapshot_id = ?1 ORDER BY online DESC, mac",
+        )?;
+        let mut rows = snapshots.query(params![since.map_or(i64::MIN, |s| s.timestamp())])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let info = info
+                .query_map(params![id], |row| {
+                    Ok(RecordedInfo {
+                        key: row.get(0)?,
+                        label: row.get(1)?,
+                        value: row.get(2)?,
+                        number: row.get(3)?,
+                        unit: row.get(4)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+            let devices = devices
+                .query_map(params![id], |row| {
+                    Ok(RecordedDevice {
+                        mac: row.get(0)?,
+                        name: row.get(1)?,
+                        online: row.get(2)?,
+                        connection: row.get(3)?,
+                        uptime_minutes: row.get(4)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+            f(Recorded {
+                time: local_time(row.get(1)?),
+                router: row.get(2)?,
+                download_bytes: row.get::<_, Option<i64>>(3)?.map(|b| b as u64),
+                upload_bytes: row.get::<_, Option<i64>>(4)?.map(|b| b as u64),
+                info,
+                devices,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Parsed values of the information `keys` per snapshot since `since`, one row per snapshot.
+    pub fn signal_table(&self, keys: &[&str], since: DateTime<Local>) -> rusqlite::Result<String> {
+        let placeholders = vec!["?"; keys.len()].join(", ");
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT s.id, s.time, i.key, i.number, i.unit FROM snapshots s \
+            JOIN info i ON i.snapshot_id = s.id \
+            WHERE s.time >= ? AND i.key IN ({}) ORDER BY s.time, s.id",
+            placeholders
+        ))?;
+        let since = since.timestamp();
+        let mut parameters: Vec<&dyn ToSql> = vec![&since];
+        parameters.extend(keys.iter().map(|k| k as &dyn ToSql));
+        let mut rows = statement.query(params_from_iter(parameters))?;
+
+        let mut units = vec![None; keys.len()];
+        let mut snapshots: Vec<(i64, i64, Vec<Option<f64>>)> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let (id, time, key): (i64, i64, String) = (row.get(0)?, row.get(1)?, row.get(2)?);
+            if snapshots.last().map(|s| s.0) != Some(id) {
+                snapshots.push((id, time, vec![None; keys.len()]));
+            }
+            let index = keys.iter().position(|k| *k == key).unwrap();
+            snapshots.last_mut().unwrap().2[index] = row.get(3)?;
+            if units[index].is_none() {
+                units[index] = row.get::<_, Option<String>>(4)?;
+            }
+        }
+
+        let mut out = format!("{:<22}", "Time");
+        for (key, unit) in keys.iter().zip(&units) {
+            let header = match unit {
+                Some(unit) => format!("{} ({})", key, unit),
+                None => key.to_string(),
+            };
+            write!(out, "{:>14}", header).unwrap();
+        }
+        out.push('\n');
+        for (_, time, values) in snapshots {
+            write!(out, "{:<22}", local_time(time).format(TIME_FORMAT)).unwrap();
+            for value in values {
+                let value = value.map_or_else(|| "-".to_string(), |v| v.to_string());
+                write!(out, "{:>14}", value).unwrap();
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Traffic per day since `since`, accounted from the counters of consecutive snapshots.
+    pub fn ledger(&self, since: DateTime<Local>) -> rusqlite::Result<UsageLedger> {
+        let mut statement = self.connection.prepare(
+            "SELECT time, download_bytes, upload_bytes FROM snapshots \
+            WHERE time >= ?1 AND download_bytes IS NOT NULL AND upload_bytes IS NOT NULL \
+            ORDER BY time, id",
+        )?;
+        let mut rows = statement.query(params![since.timestamp()])?;
+        let mut ledger = UsageLedger::default();
+        while let Some(row) = rows.next()? {
+            ledger.record(Reading {
+                time: local_time(row.get(0)?),
+                traffic: Traffic {
+                    download: row.get::<_, i64>(1)? as u64,
+                    upload: row.get::<_, i64>(2)? as u64,
+                },
+            });
+        }
+        Ok(ledger)
+    }
+
+    /// Writes the information values or device states since `since` as CSV.
+    pub fn export(
+        &self,
+        table: Table,
+        since: Option<DateTime<Local>>,
+        out: impl Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let (header, query) = match table {
+            Table::Info => (
+                ["time", "router", "key", "label", "value", "number", "unit"],
+                "SELECT s.time, s.router, i.key, i.label, i.value, i.number, i.unit \
+                FROM snapshots s JOIN info i ON i.snapshot_id = s.id \
+                WHERE s.time >= ?1 ORDER BY s.time, s.id, i.key",
+            ),
+            Table::Devices => (
+                [
+                    "time",
+                    "router",
+                    "mac",
+                    "name",
+                    "online",
+                    "connection",
+                    "uptime_minutes",
+                ],
+                "SELECT s.time, s.router, d.mac, d.name, d.online, d.connection, d.uptime_minutes \
+                FROM snapshots s JOIN devices d ON d.snapshot_id = s.id \
+                WHERE s.time >= ?1 ORDER BY s.time, s.id, d.mac",
+            ),
+        };
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(header)?;
+
+        let mut statement = self.connection.prepare(query)?;
+        let mut rows = statement.query(params![since.map_or(i64::MIN, |s| s.timestamp())])?;
+        while let Some(row) = rows.next()? {
+            let mut record = vec![local_time(row.get(0)?).to_rfc3339()];
+            for i in 1..header.len() {
+                let field = match row.get_ref(i)? {
+                    ValueRef::Null => String::new(),
+                    ValueRef::Integer(v) => v.to_string(),
+                    ValueRef::Real(v) => v.to_string(),
+                    ValueRef::Text(v) | ValueRef::Blob(v) => {
+                        String::from_utf8_lossy(v).into_owned()
+                    }
+                };
+                record.push(field);
+            }
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Traffic of `ledger` summed per period, oldest first.
+pub fn usage_table(ledger: &UsageLedger, period: Period) -> String {
+    let mut periods: BTreeMap<NaiveDate, Traffic> = BTreeMap::new();
+    for (date, traffic) in ledger.days() {
+        *periods.entry(period.start(*date)).or_default() += *traffic;
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:<30}{:>14}{:>14}{:>14}",
+        "Period", "Download", "Upload", "Total"
+    )
+    .unwrap();
+    for (start, traffic) in periods {
+        writeln!(
+            out,
+            "{:<30}{:>14}{:>14}{:>14}",
+            period.label(start),
+            format_bytes(traffic.download),
+            format_bytes(traffic.upload),
+            format_bytes(traffic.total())
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local, TimeZone};
+
+    use super::{parse_age, usage_table, History, Period, Recorded, Table};
+    use crate::{
+        counters::TrafficTotals,
+        snapshot::Snapshot,
+        usage::{Traffic, BYTES_PER_GB},
+    };
+
+    #[test]
+    fn record_and_query() {
+        assert_eq!(parse_age("24h"), Some(Duration::hours(24)));
+        assert_eq!(parse_age("7d"), Some(Duration::days(7)));
+        assert_eq!(parse_age("h"), None);
+        assert_eq!(parse_age("3x"), None);
+
+        let mut history = History::in_memory();
+        let mut snapshot = Snapshot::example();
+        // Monday and Tuesday, with a counter reset on Tuesday
+        let scrapes = [(5, 31, 12, -1.0, 1), (6, 1, 0, 4.5, 3), (6, 1, 12, 2.0, 1)];
+        for (month, day, hour, sinr, download) in scrapes {
+            snapshot.metadata.time = Local
+                .with_ymd_and_hms(2021, month, day, hour, 0, 0)
+                .unwrap();
+            snapshot
+                .info
+                .get_mut("sinr")
+                .unwrap()
+                .parsed
+                .as_mut()
+                .unwrap()
+                .value = sinr;
+            let raw = Traffic {
+                download: download * BYTES_PER_GB,
+                upload: 0,
+            };
+            snapshot.traffic = Some(TrafficTotals {
+                raw,
+                total: raw,
+                restarts: 0,
+            });
+            history.record(&snapshot).unwrap();
+        }
+
+        let since = Local.with_ymd_and_hms(2021, 6, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            history.signal_table(&["sinr", "rsrq"], since).unwrap(),
+            "Time                       sinr (dB)          rsrq\n\
+            2021-06-01 00:00:00              4.5             -\n\
+            2021-06-01 12:00:00                2             -\n"
+        );
+
+        let ledger = history.ledger(since - Duration::days(7)).unwrap();
+        assert_eq!(
+            usage_table(&ledger, Period::Day),
+            "Period                              Download        Upload         Total\n\
+            2021-06-01                           3.00 GB       0.00 GB       3.00 GB\n"
+        );
+        assert!(usage_table(&ledger, Period::Week).contains("Week 2021-W22 "));
+
+        let mut csv = Vec::new();
+        history
+            .export(Table::Devices, Some(since), &mut csv)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "time,router,mac,name,online,connection,uptime_minutes"
+        );
+        assert!(lines[1].ends_with(",192.168.8.1,AA:BB:CC:DD:EE:01,Laptop,1,5GHz,90"));
+        assert!(lines[2].ends_with(",192.168.8.1,AA:BB:CC:DD:EE:02,,0,,"));
+
+        let mut recorded = Vec::new();
+        history
+            .each_recorded(Some(since), |r| {
+                recorded.push(r);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[1], Recorded::from(&snapshot));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{snapshot::Snapshot, ConnectionType, Parsed, SERIAL_NUMBER};
+
+/// Information entries identifying the serving cell, used as tags on the `huawei_info` measurement.
+pub const CELL_ID: &str = "cell_id";
+const BAND: &str = "band";
+
+enum Field {
+    Float(f64),
+    Int(u64),
+    Bool(bool),
+}
+
+/// One line of the InfluxDB line protocol.
+struct Line {
+    measurement: &'static str,
+    tags: Vec<(&'static str, String)>,
+    fields: Vec<(String, Field)>,
+}
+
+impl Line {
+    fn new(measurement: &'static str, tags: &[(&'static str, String)]) -> Self {
+        Line {
+            measurement,
+            tags: tags.to_vec(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn tag(mut self, key: &'static str, value: Option<&String>) -> Self {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            self.tags.push((key, value.clone()));
+        }
+        self
+    }
+
+    fn field(&mut self, key: &str, value: Field) {
+        self.fields.push((key.to_string(), value));
+    }
+
+    fn float(&mut self, key: &str, value: Option<f64>) {
+        if let Some(value) = value {
+            self.field(key, Field::Float(value));
+        }
+    }
+
+    fn int(&mut self, key: &str, value: Option<u64>) {
+        if let Some(value) = value {
+            self.field(key, Field::Int(value));
+        }
+    }
+
+    fn encode(&self, timestamp: i64) -> Option<String> {
+        if self.fields.is_empty() {
+            return None;
+        }
+        let mut line = escape(self.measurement, &[',', ' ']);
+        for (key, value) in &self.tags {
+            line.push(',');
+            line.push_str(&escape(key, &[',', '=', ' ']));
+            line.push('=');
+            line.push_str(&escape(value, &[',', '=', ' ']));
+        }
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Field::Float(v) => v.to_string(),
+                    Field::Int(v) => format!("{}i", v),
+                    Field::Bool(v) => v.to_string(),
+                };
+                format!("{}={}", escape(key, &[',', '=', ' ']), value)
+            })
+            .collect();
+        Some(format!("{} {} {}", line, fields.join(","), timestamp))
+    }
+}
+
+fn escape(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Renders the snapshot as InfluxDB line protocol, one measurement per section with nanosecond timestamps.
+pub fn lines(snapshot: &Snapshot) -> Vec<String> {
+    let timestamp = snapshot.metadata.time.timestamp_nanos_opt().unwrap();
+    let info_value = |key| snapshot.info.get(key).map(|i| &i.value);
+    let router = [
+        ("router", snapshot.metadata.router_host.clone()),
+        (
+            "serial",
+            info_value(SERIAL_NUMBER).cloned().unwrap_or_default(),
+        ),
+    ];
+    let router: Vec<_> = router
+        .iter()
+        .filter(|(_, v)| !v.is_empty())
+        .cloned()
+        .collect();
+    let mut lines = Vec::new();
+
+    let mut info = Line::new("huawei_info", &router)
+        .tag("cell", info_value(CELL_ID))
+        .tag("band", info_value(BAND));
+    let mut entries: Vec<_> = snapshot.info.iter().collect();
+    entries.sort_by_key(|(key, _)| key.as_str());
+    for (key, information) in entries {
+        if let Some(Parsed { value, .. }) = &information.parsed {
+            info.field(key, Field::Float(*value));
+        }
+    }
+    lines.push(info);
+
+    let mut counts = Line::new("huawei_devices", &router);
+    for (name, _, count) in snapshot.devices.counts() {
+        counts.int(name, Some(count as u64));
+    }
+    lines.push(counts);
+
+    let devices = snapshot
+        .devices
+        .online
+        .iter()
+        .map(|d| (d, true))
+        .chain(snapshot.devices.offline.iter().map(|d| (d, false)));
+    for (dev, online) in devices {
+        let connection = dev.connection.as_ref().map(ConnectionType::name);
+        let mut device = Line::new("huawei_device", &router)
+            .tag("mac", Some(&dev.mac))
+            .tag("name", dev.name.as_ref())
+            .tag("connection", connection.as_ref());
+        device.field("online", Field::Bool(online));
+        device.int("uptime_minutes", dev.uptime.as_ref().map(|u| u.minutes));
+        lines.push(device);
+    }
+
+    if let Some(statistics) = &snapshot.statistics {
+        let mut router_statistics = Line::new("huawei_statistics", &router);
+        router_statistics.int(
+            "month_download_bytes",
+            statistics.month_download.as_ref().and_then(Parsed::bytes),
+        );
+        router_statistics.int(
+            "month_upload_bytes",
+            statistics.month_upload.as_ref().and_then(Parsed::bytes),
+        );
+        router_statistics.int("month_duration_seconds", statistics.month_duration_seconds);
+        router_statistics.int("plan_start_day", statistics.plan.start_day.map(u64::from));
+        router_statistics.int(
+            "plan_limit_bytes",
+            statistics.plan.limit.as_ref().and_then(Parsed::bytes),
+        );
+        router_statistics.float(
+            "plan_warning_threshold_percent",
+            statistics.plan.warning_threshold_percent,
+        );
+        lines.push(router_statistics);
+    }
+
+    if let Some(totals) = &snapshot.traffic {
+        let mut traffic = Line::new("huawei_traffic", &router);
+        traffic.int("download_bytes", Some(totals.total.download));
+        traffic.int("upload_bytes", Some(totals.total.upload));
+        traffic.int("restarts", Some(totals.restarts));
+        lines.push(traffic);
+    }
+
+    if let Some(rates) = &snapshot.average_rates {
+        let mut average = Line::new("huawei_average_rates", &router);
+        average.float("download_mbps", Some(rates.download_mbps));
+        average.float("upload_mbps", Some(rates.upload_mbps));
+        average.float("interval_seconds", Some(rates.seconds));
+        lines.push(average);
+    }
+
+    if let Some(usage) = &snapshot.usage {
+        let mut used = Line::new("huawei_usage", &router);
+        used.int("day_used_bytes", Some(usage.today.total()));
+        used.int("month_used_bytes", Some(usage.month.total()));
+        used.int("cycle_used_bytes", Some(usage.cycle.used.total()));
+        used.int("cycle_quota_bytes", usage.cycle.quota);
+        used.int("cycle_remaining_bytes", usage.cycle.remaining);
+        used.int("cycle_projected_bytes", Some(usage.cycle.projected));
+        lines.push(used);
+    }
+
+    if let Some(quota) = &snapshot.quota {
+        let mut status = Line::new("huawei_quota", &router);
+        status.field("data_disabled", Field::Bool(quota.data_disabled));
+        lines.push(status);
+    }
+
+    lines.iter().filter_map(|l| l.encode(timestamp)).collect()
+}
+
+/// The complete line protocol document printed by `-f influx`.
+pub fn encode(snapshot: &Snapshot) -> String {
+    let mut text = lines(snapshot).join("\n");
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, lines};
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn line_protocol() {
+        let snapshot = Snapshot::example();
+        let ts = snapshot.metadata.time.timestamp_nanos_opt().unwrap();
+        let lines = lines(&snapshot);
+        assert_eq!(
+            lines[0],
+            format!(
+                "huawei_info,router=192.168.8.1,serial=ABC123 rsrp=-109,sinr=-1,totaldownload=2048 {}",
+                ts
+            )
+        );
+        assert!(lines.contains(&format!(
+            "huawei_device,router=192.168.8.1,serial=ABC123,mac=AA:BB:CC:DD:EE:01,name=Laptop,connection=5GHz online=true,uptime_minutes=90i {}",
+            ts
+        )));
+        assert!(lines.contains(&format!(
+            "huawei_device,router=192.168.8.1,serial=ABC123,mac=AA:BB:CC:DD:EE:02 online=false {}",
+            ts
+        )));
+        assert_eq!(
+            escape("My Laptop,2=a", &[',', '=', ' ']),
+            r"My\ Laptop\,2\=a"
+        );
+    }
+}
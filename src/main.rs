@@ -1,44 +1,98 @@
-#![feature(async_closure)]
-
 use std::{
     collections::HashMap,
-    env, fs,
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    panic::AssertUnwindSafe,
+    path::Path,
     process::{Command, Stdio},
+    sync::Arc,
     time::Duration,
 };
 
-use clap::{App, Arg};
+use chrono::Local;
+use clap::{App, AppSettings, Arg, SubCommand};
 use dotenv::dotenv;
-use fantoccini::{elements::Element, ClientBuilder, Locator};
-use prometheus::{
-    Counter, CounterVec, Encoder, Gauge, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
-    TextEncoder,
-};
+use fantoccini::{elements::Element, Client, ClientBuilder, Locator};
+use futures::FutureExt;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, to_value, Map, Value};
-use tokio::time::sleep;
+use serde_json::{json, Map, Value};
+use tokio::time::{sleep, timeout};
+
+use crate::{
+    check::{CheckConfig, Status, Threshold},
+    config::Config,
+    events::{Event, Tracker},
+    history::{History, Period, Recorded, Table},
+    quota::{QuotaAction, QuotaRule, QuotaStatus},
+    sinks::{
+        file::FileConfig, stdout::StdoutConfig, ConfiguredSink, Format, RetryPolicy, SinkConfig,
+        SinkEntry,
+    },
+    snapshot::{Metadata, Snapshot},
+    state::State,
+    statistics::extract_statistics,
+    usage::{AverageRates, BillingCycle, Reading, Traffic, UsageSummary, BYTES_PER_GB},
+};
 
 #[macro_use]
 extern crate log;
 
+mod backfill;
+mod check;
+mod config;
+mod counters;
+mod events;
+mod history;
+mod influx;
+mod metrics;
+mod munin;
+mod parquet_export;
+mod protobuf;
+mod quota;
+mod sinks;
+mod snapshot;
+mod snmp;
+mod state;
+mod statistics;
+#[cfg(test)]
+mod testing;
+mod usage;
+
 const ENV_CHOMEDRIVER_PORT: &str = "CHROMEDRIVER_PORT";
 const DEFAULT_CHROMEDRIVER_PORT: u16 = 9515;
 const ENV_HUAWEI_ROUTER_HOST: &str = "HUAWEI_ROUTER_HOST";
 const DEFAULT_HUAWEI_ROUTER_HOST: &str = "192.168.8.1";
 const ENV_DEVICE_PASSWORD: &str = "HUAWEI_ROUTER_PASS";
-
-enum OutputFormats {
-    Json,
-    Prometheus,
-    Silent,
-}
+const ENV_STATE_FILE: &str = "HUAWEI_METRICS_STATE";
+const ENV_CONFIG_FILE: &str = "HUAWEI_METRICS_CONFIG";
+const ENV_HISTORY_FILE: &str = "HUAWEI_METRICS_HISTORY";
+const ENV_TEXTFILE_DIR: &str = "HUAWEI_METRICS_TEXTFILE_DIR";
+/// Name of the file written to `--textfile-dir`.
+const TEXTFILE_NAME: &str = "huawei.prom";
+const ENV_MUNIN_CACHE_SECONDS: &str = "HUAWEI_METRICS_MUNIN_CACHE_SECONDS";
+/// How long to wait for the statistics page before scraping without it.
+const STATISTICS_PAGE_TIMEOUT_SECS: u64 = 15;
+
+/// Key of the router's operation time on the device information page.
+const ROUTER_UPTIME: &str = "uptime";
+/// Keys of the device information page identifying the router.
+const SERIAL_NUMBER: &str = "serialNumber";
+const DEVICE_NAME: &str = "DeviceName";
+const SOFTWARE_VERSION: &str = "softwareVersion";
 
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
     dotenv().ok();
 
+    // Munin runs plugins through symlinks named after the graph, e.g. `huawei_signal config`
+    let mut args: Vec<String> = env::args().collect();
+    if let Some(graph) = args.first().and_then(|arg0| munin::invoked_as(arg0)) {
+        args.splice(1..1, vec!["munin".to_string(), graph.to_string()]);
+    }
+
     let matches = App::new("huawei-metrics")
         .arg(
             Arg::with_name("format")
@@ -46,7 +100,7 @@ async fn main() {
                 .takes_value(true)
                 .default_value("json")
                 .help("Output format to print on stdout")
-                .possible_values(&["json", "prometheus", "silent"]),
+                .possible_values(&["json", "prometheus", "influx", "silent"]),
         )
         .arg(
             Arg::with_name("chromedriver")
@@ -65,15 +119,429 @@ async fn main() {
                 .help("File to write json metrics to in addition to the stdout output")
                 .takes_value(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("textfile-dir")
+                .long("textfile-dir")
+                .env(ENV_TEXTFILE_DIR)
+                .help("Directory of the node_exporter textfile collector to write huawei.prom to")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .long("interval")
+                .help("Keep running and scrape every given number of seconds")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .env(ENV_CONFIG_FILE)
+                .help("TOML file configuring additional sinks")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("state")
+                .long("state")
+                .env(ENV_STATE_FILE)
+                .global(true)
+                .help("File to keep state between runs in, required for usage accounting")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("history")
+                .long("history")
+                .env(ENV_HISTORY_FILE)
+                .global(true)
+                .help("SQLite database to record every snapshot in, required for the history subcommands")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("billing-day")
+                .long("billing-day")
+                .global(true)
+                .default_value("1")
+                .help("Day of month on which the billing cycle starts")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("quota")
+                .long("quota")
+                .global(true)
+                .help("Data volume included per billing cycle in GB")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("quota-rule")
+                .long("quota-rule")
+                .help("Action once a percentage of the quota is used, e.g. 80:notify or 100:disable-data")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("quota-notify-command")
+                .long("quota-notify-command")
+                .help("Shell command run for notify quota rules")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Only logs quota rule actions instead of executing them"),
+        )
+        .subcommand(
+            SubCommand::with_name("usage")
+                .about("Prints accounted data usage from the state file without scraping")
+                .arg(
+                    Arg::with_name("days")
+                        .long("days")
+                        .default_value("7")
+                        .help("Number of days to list individually")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Scrapes once and reports the state as Nagios/Icinga plugin, exiting 0-3")
+                .arg(threshold_arg("warn-sinr", "SINR in dB below which the state is WARNING"))
+                .arg(threshold_arg("crit-sinr", "SINR in dB below which the state is CRITICAL"))
+                .arg(threshold_arg("warn-rsrp", "RSRP in dBm below which the state is WARNING"))
+                .arg(threshold_arg("crit-rsrp", "RSRP in dBm below which the state is CRITICAL"))
+                .arg(threshold_arg("warn-rsrq", "RSRQ in dB below which the state is WARNING"))
+                .arg(threshold_arg("crit-rsrq", "RSRQ in dB below which the state is CRITICAL"))
+                .arg(
+                    Arg::with_name("known-device")
+                        .long("known-device")
+                        .help("MAC address of an expected device")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("max-unknown-devices")
+                        .long("max-unknown-devices")
+                        .help("Number of online devices not given as --known-device above which the state is CRITICAL")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("munin")
+                .about("Munin plugin, also used when invoked as huawei_signal, huawei_traffic or huawei_devices")
+                .arg(
+                    Arg::with_name("graph")
+                        .required(true)
+                        .possible_values(&munin::GRAPHS),
+                )
+                .arg(Arg::with_name("mode").possible_values(&["config", "autoconf"]))
+                .arg(
+                    Arg::with_name("cache-seconds")
+                        .long("cache-seconds")
+                        .env(ENV_MUNIN_CACHE_SECONDS)
+                        .default_value("240")
+                        .help("Age up to which scraped data is reused by the other graphs")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("Queries the snapshots recorded in the history database without scraping")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("signal")
+                        .about("Lists information values per snapshot")
+                        .arg(since_arg().default_value("24h"))
+                        .arg(
+                            Arg::with_name("field")
+                                .long("field")
+                                .help("Information key to list, defaults to the signal values")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("usage")
+                        .about("Sums traffic per period")
+                        .arg(since_arg().default_value("30d"))
+                        .arg(
+                            Arg::with_name("by")
+                                .long("by")
+                                .default_value("day")
+                                .possible_values(&["day", "week", "month"])
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Writes recorded snapshots as CSV")
+                        .arg(since_arg())
+                        .arg(
+                            Arg::with_name("table")
+                                .long("table")
+                                .default_value("info")
+                                .possible_values(&["info", "devices"])
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .long("output")
+                                .short("o")
+                                .help("File to write to instead of stdout")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("backfill")
+                .about("Converts the snapshots of an archive sink directory for importing them elsewhere")
+                .arg(
+                    Arg::with_name("dir")
+                        .required(true)
+                        .help("Directory of the archive sink"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .default_value("openmetrics")
+                        .possible_values(&["openmetrics", "influx"])
+                        .takes_value(true),
+                )
+                .arg(since_arg())
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .help("File to write to instead of stdout")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("parquet")
+                .about("Writes the history database or an archive sink directory as Parquet files partitioned by day")
+                .arg(
+                    Arg::with_name("dir")
+                        .required(true)
+                        .help("Directory to write the signal, traffic and devices tables to"),
+                )
+                .arg(
+                    Arg::with_name("archive")
+                        .long("archive")
+                        .help("Archive sink directory to read instead of the history database")
+                        .takes_value(true),
+                )
+                .arg(since_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("mib")
+                .about("Prints the SNMP MIB of the values served by the agentx sink"),
+        )
+        .get_matches_from_safe(args)
+        .unwrap_or_else(|e| {
+            // Plugins report usage errors as UNKNOWN, clap would exit with CRITICAL's code
+            if e.use_stderr() && failed_in_check(&e) {
+                let reason = e.message.lines().next().unwrap_or_default();
+                println!("HUAWEI UNKNOWN - {}", reason.trim_start_matches("error: "));
+                std::process::exit(Status::Unknown.exit_code());
+            }
+            e.exit()
+        });
+
+    if matches.subcommand_matches("check").is_some() {
+        // Plugins report configuration problems as UNKNOWN instead of a panic
+        std::panic::set_hook(Box::new(|info| {
+            println!("HUAWEI UNKNOWN - {}", check::panic_message(info.payload()));
+            std::process::exit(Status::Unknown.exit_code());
+        }));
+    }
+
+    if matches.subcommand_matches("mib").is_some() {
+        print!("{}", snmp::mib());
+        return;
+    }
 
-    let format = match matches.value_of("format").unwrap() {
-        "json" => OutputFormats::Json,
-        "prometheus" => OutputFormats::Prometheus,
-        "silent" => OutputFormats::Silent,
-        _ => unreachable!(),
+    let billing_cycle = |matches: &clap::ArgMatches| BillingCycle {
+        start_day: matches
+            .value_of("billing-day")
+            .unwrap()
+            .parse()
+            .ok()
+            .filter(|d| (1..=31).contains(d))
+            .expect("Billing day must be a day of month"),
+        quota: matches.value_of("quota").map(|q| {
+            (q.parse::<f64>().expect("Quota must be a number of GB") * BYTES_PER_GB as f64) as u64
+        }),
     };
 
+    if let Some(usage_matches) = matches.subcommand_matches("usage") {
+        let state = State::load(
+            usage_matches
+                .value_of("state")
+                .expect("Usage accounting needs a state file, see --state"),
+        );
+        let days = usage_matches
+            .value_of("days")
+            .unwrap()
+            .parse()
+            .expect("Unable to parse number of days");
+        print!(
+            "{}",
+            usage::usage_table(
+                &state.usage,
+                &billing_cycle(usage_matches),
+                Local::now(),
+                days
+            )
+        );
+        return;
+    }
+    if let Some(backfill_matches) = matches.subcommand_matches("backfill") {
+        let since = backfill_matches.value_of("since").map(|age| {
+            Local::now()
+                - history::parse_age(age).expect("Age must be a number with unit s, m, h, d or w")
+        });
+        let snapshots =
+            backfill::snapshots(Path::new(backfill_matches.value_of("dir").unwrap()), since)
+                .expect("Unable to read archive directory");
+        let mut out: Box<dyn Write> = match backfill_matches.value_of("output") {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(path).expect("Unable to create output file"),
+            )),
+            None => Box::new(io::stdout()),
+        };
+        match backfill_matches.value_of("format").unwrap() {
+            "openmetrics" => out
+                .write_all(backfill::openmetrics(snapshots).as_bytes())
+                .and_then(|_| out.flush()),
+            "influx" => backfill::influx(snapshots, out),
+            _ => unreachable!(),
+        }
+        .expect("Unable to write backfill data");
+        return;
+    }
+    if let Some(parquet_matches) = matches.subcommand_matches("parquet") {
+        let since = parquet_matches.value_of("since").map(|age| {
+            parquet_export::first_full_day(
+                Local::now()
+                    - history::parse_age(age)
+                        .expect("Age must be a number with unit s, m, h, d or w"),
+            )
+        });
+        let dir = parquet_matches.value_of("dir").unwrap();
+        let mut exporter = parquet_export::Exporter::new(Path::new(dir));
+        match parquet_matches.value_of("archive") {
+            Some(archive) => backfill::snapshots(Path::new(archive), since)
+                .expect("Unable to read archive directory")
+                .try_for_each(|snapshot| exporter.add(&Recorded::from(&snapshot))),
+            None => History::open(
+                parquet_matches
+                    .value_of("history")
+                    .expect("Parquet export needs --archive or a history database, see --history"),
+            )
+            .each_recorded(since, |recorded| exporter.add(&recorded)),
+        }
+        .expect("Unable to write Parquet files");
+        let files = exporter.finish().expect("Unable to write Parquet files");
+        info!("Wrote {} Parquet file(s) to {}", files.len(), dir);
+        return;
+    }
+
+    if let Some(history_matches) = matches.subcommand_matches("history") {
+        let history = History::open(
+            history_matches
+                .value_of("history")
+                .expect("History queries need a database, see --history"),
+        );
+        let since = |matches: &clap::ArgMatches| {
+            matches.value_of("since").map(|age| {
+                Local::now()
+                    - history::parse_age(age)
+                        .expect("Age must be a number with unit s, m, h, d or w")
+            })
+        };
+        match history_matches.subcommand() {
+            ("signal", Some(signal_matches)) => {
+                let fields = signal_matches
+                    .values_of("field")
+                    .map(|fields| fields.collect())
+                    .unwrap_or_else(|| vec!["rsrp", "rsrq", "rssi", "sinr"]);
+                print!(
+                    "{}",
+                    history
+                        .signal_table(&fields, since(signal_matches).unwrap())
+                        .expect("Unable to query history database")
+                );
+            }
+            ("usage", Some(usage_matches)) => {
+                let period = match usage_matches.value_of("by").unwrap() {
+                    "day" => Period::Day,
+                    "week" => Period::Week,
+                    "month" => Period::Month,
+                    _ => unreachable!(),
+                };
+                let ledger = history
+                    .ledger(since(usage_matches).unwrap())
+                    .expect("Unable to query history database");
+                print!("{}", history::usage_table(&ledger, period));
+            }
+            ("export", Some(export_matches)) => {
+                let table = match export_matches.value_of("table").unwrap() {
+                    "info" => Table::Info,
+                    "devices" => Table::Devices,
+                    _ => unreachable!(),
+                };
+                let since = since(export_matches);
+                let result = match export_matches.value_of("output") {
+                    Some(path) => history.export(
+                        table,
+                        since,
+                        File::create(path).expect("Unable to create export file"),
+                    ),
+                    None => history.export(table, since, io::stdout()),
+                };
+                result.expect("Unable to export history");
+            }
+            _ => unreachable!(),
+        }
+        return;
+    }
+    let billing_cycle = billing_cycle(&matches);
+    let quota_rules = matches
+        .values_of("quota-rule")
+        .map(|rules| {
+            rules
+                .map(|r| r.parse::<QuotaRule>().unwrap_or_else(|e| panic!("{}", e)))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let dry_run = matches.is_present("dry-run");
+
+    if let Some(munin_matches) = matches.subcommand_matches("munin") {
+        let graph = munin_matches.value_of("graph").unwrap();
+        let max_age = chrono::Duration::seconds(
+            munin_matches
+                .value_of("cache-seconds")
+                .unwrap()
+                .parse()
+                .expect("Cache duration must be a number of seconds"),
+        );
+        if munin_matches.value_of("mode") == Some("autoconf") {
+            if env::var_os(ENV_DEVICE_PASSWORD).is_some() {
+                println!("yes");
+            } else {
+                println!("no ({} not set)", ENV_DEVICE_PASSWORD);
+            }
+            return;
+        }
+        if let Some(cache) = munin::Cache::load(max_age) {
+            debug!("Using cached data from {}", cache.time);
+            print!(
+                "{}",
+                cache.graph(graph).output(munin_matches.value_of("mode"))
+            );
+            return;
+        }
+    }
+
     let port: u16 = {
         let v = env::var(ENV_CHOMEDRIVER_PORT);
         match v {
@@ -109,249 +577,542 @@ async fn main() {
 
     info!("Found password in env: {}", ENV_DEVICE_PASSWORD);
 
-    debug!("Connecting to webdriver");
-
-    let mut capabilities = Map::new();
-    let mut chrome_options = Map::new();
+    // Checks only look at the router, they neither act on quota rules nor update the state
+    let read_only = matches.subcommand_matches("check").is_some();
+    let scraper = Scraper {
+        port,
+        router_host,
+        router_pass,
+        state_path: matches
+            .value_of("state")
+            .filter(|_| !read_only)
+            .map(str::to_owned),
+        billing_cycle,
+        quota_rules: if read_only { Vec::new() } else { quota_rules },
+        dry_run,
+        notify_command: matches.value_of("quota-notify-command").map(str::to_owned),
+    };
 
-    chrome_options.insert("args".to_string(), json!(["--headless"]));
+    if let Some(munin_matches) = matches.subcommand_matches("munin") {
+        let snapshot = scraper.scrape().await;
+        let cache = munin::Cache {
+            time: snapshot.metadata.time,
+            graphs: munin::graphs(&snapshot),
+        };
+        cache.save();
+        print!(
+            "{}",
+            cache
+                .graph(munin_matches.value_of("graph").unwrap())
+                .output(munin_matches.value_of("mode"))
+        );
 
-    if let Ok(bin) = std::env::var("CHROME_BINARY") {
-        chrome_options.insert("binary".to_string(), json!(bin));
+        if let Some(mut c) = chromedriver {
+            c.kill().ok();
+        }
+        return;
     }
 
-    capabilities.insert(
-        "goog:chromeOptions".to_string(),
-        Value::Object(chrome_options),
-    );
+    if let Some(check_matches) = matches.subcommand_matches("check") {
+        let threshold = |name: String| {
+            check_matches
+                .value_of(&name)
+                .map(|v: &str| v.parse().expect("Thresholds must be numbers"))
+        };
+        let check_config = CheckConfig {
+            thresholds: ["sinr", "rsrp", "rsrq"]
+                .iter()
+                .map(|key| Threshold {
+                    key,
+                    warn: threshold(format!("warn-{}", key)),
+                    crit: threshold(format!("crit-{}", key)),
+                })
+                .filter(|t| t.warn.is_some() || t.crit.is_some())
+                .collect(),
+            known_devices: check_matches
+                .values_of("known-device")
+                .map(|macs| macs.map(str::to_owned).collect())
+                .unwrap_or_default(),
+            max_unknown_devices: check_matches.value_of("max-unknown-devices").map(|m| {
+                m.parse()
+                    .expect("Maximum number of unknown devices must be a number")
+            }),
+        };
 
-    let mut c = ClientBuilder::native()
-        .capabilities(capabilities)
-        .connect(&format!("http://localhost:{}", port))
-        .await
-        .expect("failed to connect to WebDriver");
+        // Scraping panics on any trouble with the router, which makes the state UNKNOWN
+        std::panic::set_hook(Box::new(|info| debug!("{}", info)));
+        let (status, output) = match tokio::spawn(async move { scraper.scrape().await }).await {
+            Ok(snapshot) => check::check(&snapshot, &check_config),
+            Err(e) => (
+                Status::Unknown,
+                format!(
+                    "HUAWEI UNKNOWN - Scraping the router failed: {}",
+                    e.try_into_panic().map_or_else(
+                        |_| "cancelled".to_owned(),
+                        |p| check::panic_message(&*p).to_owned()
+                    )
+                ),
+            ),
+        };
+        println!("{}", output);
 
-    debug!("Navigating to router web interface");
-    c.goto(&format!("http://{}/html/index.html", router_host))
-        .await
-        .unwrap();
+        if let Some(mut c) = chromedriver {
+            c.kill().ok();
+        }
+        std::process::exit(status.exit_code());
+    }
 
-    c.find(Locator::Id("login_password"))
-        .await
-        .unwrap()
-        .send_keys(&router_pass)
-        .await
-        .unwrap();
-    debug!("Entered password");
+    let config = matches
+        .value_of("config")
+        .map(Config::load)
+        .unwrap_or_default();
+
+    let mut sink_entries = Vec::new();
+    // The config file replaces the default stdout output unless a format is explicitly requested
+    if config.sinks.is_empty() || matches.occurrences_of("format") > 0 {
+        let format = match matches.value_of("format").unwrap() {
+            "json" => Some(Format::Json),
+            "prometheus" => Some(Format::Prometheus),
+            "influx" => Some(Format::Influx),
+            "silent" => None,
+            _ => unreachable!(),
+        };
+        if let Some(format) = format {
+            sink_entries.push(SinkEntry {
+                name: None,
+                retry: RetryPolicy::default(),
+                config: SinkConfig::Stdout(StdoutConfig { format }),
+            });
+        }
+    }
+    for (option, format) in [
+        ("prometheus-out", Format::Prometheus),
+        ("json-out", Format::Json),
+    ] {
+        if let Some(filepath) = matches.value_of(option) {
+            sink_entries.push(SinkEntry {
+                name: Some(option.to_string()),
+                retry: RetryPolicy::default(),
+                config: SinkConfig::File(FileConfig {
+                    path: filepath.into(),
+                    format,
+                }),
+            });
+        }
+    }
+    if let Some(dir) = matches.value_of("textfile-dir") {
+        sink_entries.push(SinkEntry {
+            name: Some("textfile-dir".to_string()),
+            retry: RetryPolicy::default(),
+            config: SinkConfig::File(FileConfig {
+                path: Path::new(dir).join(TEXTFILE_NAME),
+                format: Format::Prometheus,
+            }),
+        });
+    }
+    sink_entries.extend(config.sinks);
+    let sinks: Vec<_> = sink_entries.into_iter().map(ConfiguredSink::new).collect();
+
+    let interval = matches
+        .value_of("interval")
+        .map(|secs| parse_interval(secs).unwrap_or_else(|e| panic!("{}", e)));
+
+    let mut history = matches.value_of("history").map(History::open);
+
+    let scraper = Arc::new(scraper);
+    let mut tracker = Tracker::default();
+    let mut failed_sinks;
+    let mut scrape_failed;
+    let mut stopped = false;
+    loop {
+        // A failed scrape panics, running it as a task lets the next interval try again
+        let scrape = tokio::spawn({
+            let scraper = scraper.clone();
+            async move { scraper.scrape().await }
+        });
+        let events = match scrape.await {
+            Ok(snapshot) => {
+                scrape_failed = false;
+                if let Some(history) = history.as_mut() {
+                    if let Err(e) = history.record(&snapshot) {
+                        warn!("Unable to record snapshot in history database: {:#}", e);
+                    }
+                }
+                failed_sinks = sinks::dispatch(&sinks, &snapshot).await;
+                match scraper.state_path.as_deref() {
+                    // Kept in the state file so runs from cron see the changes since the last one
+                    Some(state_path) => {
+                        let mut state = State::load(state_path);
+                        let events = state.events.update(&snapshot);
+                        state.save(state_path);
+                        events
+                    }
+                    None => tracker.update(&snapshot),
+                }
+            }
+            Err(e) => {
+                scrape_failed = true;
+                failed_sinks = 0;
+                let reason = e.try_into_panic().map_or_else(
+                    |_| "cancelled".to_owned(),
+                    |p| check::panic_message(&*p).to_owned(),
+                );
+                vec![Event::scrape_failed(
+                    Local::now(),
+                    &scraper.router_host,
+                    &reason,
+                )]
+            }
+        };
+        for event in &events {
+            info!("Event {}: {}", event.kind.name(), event.message);
+        }
+        failed_sinks += sinks::dispatch_events(&sinks, &events).await;
+
+        match interval {
+            None => break,
+            Some(interval) => {
+                debug!("Next scrape in {:?}", interval);
+                tokio::select! {
+                    _ = sleep(interval) => {}
+                    _ = shutdown_signal() => {
+                        info!("Shutting down");
+                        stopped = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
 
-    c.find(Locator::Id("login_btn"))
-        .await
-        .unwrap()
-        .click()
-        .await
-        .unwrap();
-    debug!("Clicked login button");
+    // A single run is typically one of many from cron, sinks keep their state in between
+    if stopped {
+        sinks::shutdown(&sinks).await;
+    }
 
-    debug!("Starting to wait for #menu_top_advanceset");
-    c.wait_for_find(Locator::Id("menu_top_advanceset"))
-        .await
-        .unwrap();
+    if let Some(mut c) = chromedriver {
+        info!("Killing own chromedriver");
+        c.kill().ok();
+    };
 
-    debug!("Found advanced menu, navigating to device information page");
-    c.goto(&format!(
-        "http://{}/html/content.html#deviceinformation",
-        router_host
-    ))
-    .await
-    .unwrap();
+    if scrape_failed {
+        error!("Scraping the router failed");
+        std::process::exit(1);
+    }
+    if failed_sinks > 0 {
+        error!("{} sink(s) failed", failed_sinks);
+        std::process::exit(1);
+    }
+}
 
-    debug!("Waiting for device information page content");
-    c.wait_for_find(Locator::Id("deviceinformation_page"))
-        .await
-        .unwrap();
+/// Whether clap failed on the arguments of the `check` subcommand, as told by the usage it
+/// prints for the subcommand it was parsing, e.g. `huawei-metrics check [OPTIONS]`.
+fn failed_in_check(error: &clap::Error) -> bool {
+    error
+        .message
+        .lines()
+        .skip_while(|line| !line.contains("USAGE:"))
+        .nth(1)
+        .and_then(|usage| usage.split_whitespace().nth(1))
+        == Some("check")
+}
 
-    info!("Successfully navigated to device information page");
-    debug!("Sleeping 2s to ensure all data has loaded");
-    sleep(Duration::from_millis(2000)).await;
+/// Time between two scrapes in `--interval` mode, at least a second.
+fn parse_interval(secs: &str) -> Result<Duration, String> {
+    match secs.parse() {
+        Ok(0) => Err("Interval must be at least one second".to_string()),
+        Ok(secs) => Ok(Duration::from_secs(secs)),
+        Err(_) => Err(format!(
+            "Interval must be a number of seconds, got {}",
+            secs
+        )),
+    }
+}
 
-    let info =
-        extract_information(&mut c.find(Locator::Id("deviceinformation_page")).await.unwrap())
-            .await;
+fn since_arg() -> Arg<'static, 'static> {
+    Arg::with_name("since")
+        .long("since")
+        .help("Only snapshots up to this old, e.g. 30m, 24h or 7d")
+        .takes_value(true)
+}
 
-    debug!("Navigating to device management page");
-    c.goto(&format!(
-        "http://{}/html/content.html#devicemanagement",
-        router_host
-    ))
-    .await
-    .unwrap();
-
-    debug!("Sleeping 4s to allow device management page to load");
-    sleep(Duration::from_millis(4000)).await;
-
-    let mut info_map = Map::new();
-
-    let devices =
-        extract_devices(&mut c.find(Locator::Id("devicemanagement_page")).await.unwrap()).await;
-
-    info_map.insert("devices".to_string(), to_value(&devices).unwrap());
-    for (k, v) in &info {
-        let old_data = info_map.insert(k.to_owned(), to_value(v).unwrap());
-        if old_data.is_some() {
-            error!(
-                "Somehow overwrote data when copying one map to another: {}",
-                k
-            );
+fn threshold_arg(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(name)
+        .help(help)
+        .takes_value(true)
+        .allow_hyphen_values(true)
+}
+
+/// Resolves once the process is asked to terminate.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term.recv() => {}
         }
     }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.unwrap();
+}
 
-    let json_out = serde_json::to_string_pretty(&info_map).unwrap();
-    let new_opt = |name: &str, help: &str| {
-        Opts::new(name.to_string(), help.to_string()).namespace("huawei_metrics")
-    };
-    let prometheus_out = {
-        let r = Registry::new();
+/// Logs into the router and gathers one snapshot per call.
+struct Scraper {
+    port: u16,
+    router_host: String,
+    router_pass: String,
+    state_path: Option<String>,
+    billing_cycle: BillingCycle,
+    quota_rules: Vec<QuotaRule>,
+    dry_run: bool,
+    notify_command: Option<String>,
+}
 
-        for (name, help, num) in [
-            (
-                "online_devices",
-                "Number of online devices",
-                devices.online.len() as i64,
-            ),
-            (
-                "offline_devices",
-                "Number of offline devices",
-                devices.offline.len() as i64,
-            ),
-            (
-                "total_devices",
-                "Number of total devices",
-                (devices.offline.len() + devices.online.len()) as i64,
-            ),
-            (
-                "wifi_devices",
-                "Number of wifi devices",
-                devices
-                    .online
-                    .iter()
-                    .filter(|d| matches!(d.connection, Some(ConnectionType::Wifi(_))))
-                    .count() as i64,
-            ),
-            (
-                "wifi_2ghz_devices",
-                "Number of 2.4 GHz wifi devices",
-                devices
-                    .online
-                    .iter()
-                    .filter(|d| {
-                        matches!(d.connection, Some(ConnectionType::Wifi(Frequency::W2_4GHz)))
-                    })
-                    .count() as i64,
-            ),
-            (
-                "wifi_5ghz_devices",
-                "Number of 5 GHz wifi devices",
-                devices
-                    .online
-                    .iter()
-                    .filter(|d| {
-                        matches!(d.connection, Some(ConnectionType::Wifi(Frequency::W5GHz)))
-                    })
-                    .count() as i64,
-            ),
-        ] {
-            let opts = new_opt(name, help);
-            let gauge = IntGauge::with_opts(opts).unwrap();
-            gauge.set(num);
-            r.register(Box::new(gauge)).unwrap();
-        }
+impl Scraper {
+    async fn scrape(&self) -> Snapshot {
+        debug!("Connecting to webdriver");
 
-        let device_vec = IntCounterVec::new(
-            new_opt("device_uptime_min", "Devices"),
-            &["connection", "mac", "friendly_name"],
-        )
-        .unwrap();
-        for dev in devices.online.iter() {
-            device_vec
-                .with_label_values(&["online", &dev.mac, dev.name.as_ref().unwrap_or(&dev.mac)])
-                .inc_by(dev.uptime.as_ref().map_or(0, |u| u.minutes));
+        let mut capabilities = Map::new();
+        let mut chrome_options = Map::new();
+
+        chrome_options.insert("args".to_string(), json!(["--headless"]));
+
+        if let Ok(bin) = std::env::var("CHROME_BINARY") {
+            chrome_options.insert("binary".to_string(), json!(bin));
         }
 
-        for dev in devices.offline.iter() {
-            device_vec
-                .with_label_values(&["offline", &dev.mac, dev.name.as_ref().unwrap_or(&dev.mac)])
-                .inc_by(0);
+        capabilities.insert(
+            "goog:chromeOptions".to_string(),
+            Value::Object(chrome_options),
+        );
+
+        let mut c = ClientBuilder::native()
+            .capabilities(capabilities)
+            .connect(&format!("http://localhost:{}", self.port))
+            .await
+            .expect("failed to connect to WebDriver");
+
+        // Chromedriver keeps the browser of a session running until it is closed, so the
+        // session is closed before a failed scrape's panic is passed on
+        let result = AssertUnwindSafe(self.scrape_with(&mut c))
+            .catch_unwind()
+            .await;
+        debug!("Closing window");
+        if let Err(e) = c.close_window().await {
+            warn!("Unable to close window: {:#}", e);
+        }
+        if let Err(e) = c.close().await {
+            warn!("Unable to close WebDriver session: {:#}", e);
+        }
+        match result {
+            Ok(snapshot) => snapshot,
+            Err(panic) => std::panic::resume_unwind(panic),
         }
-        r.register(Box::new(device_vec)).unwrap();
+    }
+
+    async fn scrape_with(&self, c: &mut Client) -> Snapshot {
+        debug!("Navigating to router web interface");
+        c.goto(&format!("http://{}/html/index.html", self.router_host))
+            .await
+            .unwrap();
+
+        c.find(Locator::Id("login_password"))
+            .await
+            .unwrap()
+            .send_keys(&self.router_pass)
+            .await
+            .unwrap();
+        debug!("Entered password");
+
+        c.find(Locator::Id("login_btn"))
+            .await
+            .unwrap()
+            .click()
+            .await
+            .unwrap();
+        debug!("Clicked login button");
+
+        debug!("Starting to wait for #menu_top_advanceset");
+        c.wait_for_find(Locator::Id("menu_top_advanceset"))
+            .await
+            .unwrap();
+
+        debug!("Found advanced menu, navigating to device information page");
+        c.goto(&format!(
+            "http://{}/html/content.html#deviceinformation",
+            self.router_host
+        ))
+        .await
+        .unwrap();
+
+        debug!("Waiting for device information page content");
+        c.wait_for_find(Locator::Id("deviceinformation_page"))
+            .await
+            .unwrap();
+
+        info!("Successfully navigated to device information page");
+        debug!("Sleeping 2s to ensure all data has loaded");
+        sleep(Duration::from_millis(2000)).await;
+
+        let info =
+            extract_information(&mut c.find(Locator::Id("deviceinformation_page")).await.unwrap())
+                .await;
+
+        debug!("Navigating to device management page");
+        c.goto(&format!(
+            "http://{}/html/content.html#devicemanagement",
+            self.router_host
+        ))
+        .await
+        .unwrap();
+
+        debug!("Sleeping 4s to allow device management page to load");
+        sleep(Duration::from_millis(4000)).await;
+
+        let devices =
+            extract_devices(&mut c.find(Locator::Id("devicemanagement_page")).await.unwrap()).await;
+
+        debug!("Navigating to statistics page");
+        c.goto(&format!(
+            "http://{}/html/content.html#statistic",
+            self.router_host
+        ))
+        .await
+        .unwrap();
+
+        debug!("Waiting for statistics page content");
+        // Not every router has the page, the rest of the scrape is still useful without it
+        let statistics = match timeout(
+            Duration::from_secs(STATISTICS_PAGE_TIMEOUT_SECS),
+            c.wait_for_find(Locator::Id("statistic_page")),
+        )
+        .await
+        {
+            Ok(Ok(mut page)) => {
+                debug!("Sleeping 2s to ensure all data has loaded");
+                sleep(Duration::from_millis(2000)).await;
+                Some(extract_statistics(&mut page).await)
+            }
+            Ok(Err(e)) => {
+                warn!("Unable to load statistics page: {:#}", e);
+                None
+            }
+            Err(_) => {
+                warn!(
+                    "Statistics page did not load within {}s, skipping it",
+                    STATISTICS_PAGE_TIMEOUT_SECS
+                );
+                None
+            }
+        };
+
+        let now = Local::now();
+        let mut state = self
+            .state_path
+            .as_deref()
+            .map(|state_path| (state_path, State::load(state_path)));
+        let raw_traffic = match (
+            info.get("totaldownload").and_then(Information::bytes),
+            info.get("totalupload").and_then(Information::bytes),
+        ) {
+            (Some(download), Some(upload)) => Some(Traffic { download, upload }),
+            _ => {
+                if state.is_some() {
+                    warn!("Traffic counters not found, skipping usage accounting");
+                }
+                None
+            }
+        };
 
-        for (label, value) in &info {
-            if let Some(Parsed {
-                value: numeric_value,
-                unit,
-            }) = &value.parsed
+        let totals = state.as_mut().zip(raw_traffic).map(|((_, state), raw)| {
+            let uptime = info
+                .get(ROUTER_UPTIME)
+                .and_then(|i| MinuteCounter::try_from_str(&i.value, false))
+                .map(|u| u.minutes);
+            state.counters.update(raw, uptime)
+        });
+        let mut rates = None;
+        let usage = state
+            .as_mut()
+            .zip(totals.as_ref())
+            .map(|((_, state), totals)| {
+                let reading = Reading {
+                    time: now,
+                    traffic: totals.total,
+                };
+                rates = state
+                    .usage
+                    .last()
+                    .and_then(|previous| AverageRates::between(previous, &reading));
+                state.usage.record(reading);
+                UsageSummary::new(&state.usage, &self.billing_cycle, now)
+            });
+
+        let mut quota_actions = Vec::new();
+        if let (Some((_, state)), Some(usage)) = (state.as_mut(), &usage) {
+            for action in state
+                .quota
+                .plan(&self.quota_rules, &usage.cycle, self.dry_run)
             {
-                let opts = new_opt(
-                    &format!(
-                        "{}_{}",
-                        label.to_ascii_lowercase(),
-                        unit.to_ascii_lowercase()
-                    ),
-                    &value.label.clone(),
+                warn!(
+                    "Quota action {} for threshold {}: {} used in cycle starting {}",
+                    action.name(),
+                    action.threshold(),
+                    usage::format_bytes(usage.cycle.used.total()),
+                    usage.cycle.start
                 );
-                match unit.as_str() {
-                    "Mbps" | "Kbps" | "Bps" | "dBm" | "dB" => {
-                        let gauge = Gauge::with_opts(opts).unwrap();
-                        gauge.set(*numeric_value);
-                        r.register(Box::new(gauge)).unwrap();
-                    }
-                    "MB" | "GB" | "KB" | "B" => {
-                        let counter = Counter::with_opts(opts).unwrap();
-                        counter.inc_by(*numeric_value);
-                        r.register(Box::new(counter)).unwrap();
-                    }
-                    _ => {
-                        warn!(
-                            "Skipping {:?} because of unknown unit to metric conversion",
-                            info
-                        );
+                if self.dry_run {
+                    info!("Dry run, not executing {}", action.name());
+                } else {
+                    let result = match &action {
+                        QuotaAction::Notify { threshold } => match self.notify_command.as_deref() {
+                            Some(command) => quota::notify(command, threshold, &usage.cycle),
+                            None => Ok(()),
+                        },
+                        QuotaAction::DisableData { .. } => {
+                            quota::set_mobile_data(c, &self.router_host, false).await
+                        }
+                        QuotaAction::EnableData => {
+                            quota::set_mobile_data(c, &self.router_host, true).await
+                        }
+                    };
+                    if let Err(e) = result {
+                        error!("Quota action {} failed: {}", action.name(), e);
+                        continue;
                     }
                 }
+                state.quota.record(&action, self.dry_run);
+                quota_actions.push(action);
             }
         }
 
-        let mut buffer = vec![];
-        let encoder = TextEncoder::new();
-        let metric_families = r.gather();
-        encoder.encode(&metric_families, &mut buffer).unwrap();
-        String::from_utf8(buffer).unwrap()
-    };
-
-    match format {
-        OutputFormats::Json => {
-            println!("{}", json_out);
-        }
-        OutputFormats::Prometheus => {
-            println!("{}", prometheus_out);
+        if let Some((state_path, state)) = &state {
+            state.save(state_path);
         }
-        _ => {}
-    }
 
-    for (option, content) in [("prometheus-out", prometheus_out), ("json-out", json_out)] {
-        if let Some(filepath) = matches.value_of(option) {
-            trace!("Outputting {} to {}", option, filepath);
-            fs::write(filepath, content).unwrap();
+        Snapshot {
+            metadata: Metadata {
+                time: now,
+                router_host: self.router_host.clone(),
+                exporter_version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            info,
+            devices,
+            statistics,
+            traffic: totals,
+            average_rates: rates,
+            usage,
+            quota: state.as_ref().map(|(_, state)| QuotaStatus {
+                data_disabled: state.quota.data_disabled,
+                dry_run: self.dry_run,
+                actions: quota_actions,
+                executed: state.quota.executed.clone(),
+            }),
         }
     }
-
-    debug!("Closing window");
-    c.close_window().await.unwrap();
-    c.close().await.unwrap();
-    drop(c);
-
-    if let Some(mut c) = chromedriver {
-        info!("Killing own chromedriver");
-        c.kill().ok();
-    };
 }
 
 /*
@@ -375,18 +1136,82 @@ struct Information {
     hidden: bool,
 }
 
+impl Information {
+    fn bytes(&self) -> Option<u64> {
+        self.parsed.as_ref()?.bytes()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Parsed {
     value: f64,
     unit: String,
 }
 
+impl Parsed {
+    /// Converts data volumes to bytes, the router uses binary prefixes.
+    fn bytes(&self) -> Option<u64> {
+        let factor = match self.unit.as_str() {
+            "GB" => 1024 * 1024 * 1024,
+            "MB" => 1024 * 1024,
+            "KB" => 1024,
+            "B" => 1,
+            _ => return None,
+        };
+        Some((self.value * factor as f64) as u64)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct DeviceOverview {
     online: Vec<Device>,
     offline: Vec<Device>,
 }
 
+impl DeviceOverview {
+    /// Device counts as `(name, description, count)`.
+    fn counts(&self) -> [(&'static str, &'static str, usize); 6] {
+        let online_on = |matches_connection: fn(&Option<ConnectionType>) -> bool| {
+            self.online
+                .iter()
+                .filter(|d| matches_connection(&d.connection))
+                .count()
+        };
+        [
+            (
+                "online_devices",
+                "Number of online devices",
+                self.online.len(),
+            ),
+            (
+                "offline_devices",
+                "Number of offline devices",
+                self.offline.len(),
+            ),
+            (
+                "total_devices",
+                "Number of total devices",
+                self.offline.len() + self.online.len(),
+            ),
+            (
+                "wifi_devices",
+                "Number of wifi devices",
+                online_on(|c| matches!(c, Some(ConnectionType::Wifi(_)))),
+            ),
+            (
+                "wifi_2ghz_devices",
+                "Number of 2.4 GHz wifi devices",
+                online_on(|c| matches!(c, Some(ConnectionType::Wifi(Frequency::W2_4GHz)))),
+            ),
+            (
+                "wifi_5ghz_devices",
+                "Number of 5 GHz wifi devices",
+                online_on(|c| matches!(c, Some(ConnectionType::Wifi(Frequency::W5GHz)))),
+            ),
+        ]
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Device {
     connection: Option<ConnectionType>,
@@ -405,6 +1230,17 @@ enum ConnectionType {
     Other(String),
 }
 
+impl ConnectionType {
+    /// The serialized form, e.g. `5GHz` for wifi devices.
+    fn name(&self) -> String {
+        match self {
+            ConnectionType::Wifi(Frequency::W2_4GHz) => "2.4GHz".to_string(),
+            ConnectionType::Wifi(Frequency::W5GHz) => "5GHz".to_string(),
+            ConnectionType::Other(other) => other.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 enum Frequency {
     #[serde(rename = "2.4GHz")]
@@ -635,24 +1471,7 @@ async fn extract_information(info_page: &mut Element) -> HashMap<String, Informa
             }
         };
 
-        let parsed = {
-            let mut parsed = None;
-            for unit in ["dB", "dBm", "GB", "MB", "KB", "Gbps", "Mbps", "Kbps", "B"] {
-                parsed = try_parse(&value, unit);
-                if parsed.is_some() {
-                    break;
-                }
-            }
-            if let Some(Parsed { value, unit }) = parsed.clone() {
-                if unit == "Kbps" {
-                    parsed = Some(Parsed {
-                        value: value / 1024f64,
-                        unit: "Mbps".to_string(),
-                    });
-                }
-            }
-            parsed
-        };
+        let parsed = parse_value(&value);
 
         let row_info = Information {
             label_id: label_id.clone(),
@@ -669,6 +1488,26 @@ async fn extract_information(info_page: &mut Element) -> HashMap<String, Informa
     info
 }
 
+/// Parses values with a known unit, rates are normalized to Mbps.
+fn parse_value(value: &str) -> Option<Parsed> {
+    let mut parsed = None;
+    for unit in ["dB", "dBm", "GB", "MB", "KB", "Gbps", "Mbps", "Kbps", "B"] {
+        parsed = try_parse(value, unit);
+        if parsed.is_some() {
+            break;
+        }
+    }
+    if let Some(Parsed { value, unit }) = parsed.clone() {
+        if unit == "Kbps" {
+            parsed = Some(Parsed {
+                value: value / 1024f64,
+                unit: "Mbps".to_string(),
+            });
+        }
+    }
+    parsed
+}
+
 fn try_parse(value: impl AsRef<str>, unit: impl AsRef<str>) -> Option<Parsed> {
     let value = value.as_ref();
     let unit = unit.as_ref();
@@ -684,7 +1523,40 @@ fn try_parse(value: impl AsRef<str>, unit: impl AsRef<str>) -> Option<Parsed> {
 
 #[cfg(test)]
 mod tests {
-    use crate::MinuteCounter;
+    use std::time::Duration;
+
+    use clap::{App, Arg, SubCommand};
+
+    use crate::{failed_in_check, parse_interval, MinuteCounter};
+
+    #[test]
+    fn usage_errors_of_check() {
+        let app = || {
+            App::new("huawei-metrics")
+                .arg(Arg::with_name("state").long("state").takes_value(true))
+                .subcommand(SubCommand::with_name("check"))
+                .subcommand(
+                    SubCommand::with_name("history")
+                        .arg(Arg::with_name("field").long("field").takes_value(true)),
+                )
+        };
+        let failed = |args: &[&str]| {
+            let error = app()
+                .get_matches_from_safe(["huawei-metrics"].iter().chain(args))
+                .unwrap_err();
+            failed_in_check(&error)
+        };
+        assert!(failed(&["check", "--bogus"]));
+        assert!(failed(&["--state", "check", "check", "--bogus"]));
+        assert!(!failed(&["history", "--field", "check", "--bogus"]));
+    }
+
+    #[test]
+    fn interval() {
+        assert_eq!(parse_interval("60"), Ok(Duration::from_secs(60)));
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("1m").is_err());
+    }
 
     #[test]
     fn minute_counter() {
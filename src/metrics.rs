@@ -0,0 +1,270 @@
+use prometheus::{
+    Counter, Encoder, Gauge, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use crate::{snapshot::Snapshot, Parsed};
+
+fn new_opt(name: &str, help: &str) -> Opts {
+    Opts::new(name.to_string(), help.to_string()).namespace("huawei_metrics")
+}
+
+/// Builds a registry with all metrics derived from `snapshot`.
+pub fn registry(snapshot: &Snapshot) -> Registry {
+    let r = Registry::new();
+    let devices = &snapshot.devices;
+    let info = &snapshot.info;
+
+    // Lets alerts on a textfile or pushed group tell stale data from a quiet router
+    let last_success = IntGauge::with_opts(new_opt(
+        "last_success_timestamp_seconds",
+        "Time of the scrape the metrics are from",
+    ))
+    .unwrap();
+    last_success.set(snapshot.metadata.time.timestamp());
+    r.register(Box::new(last_success)).unwrap();
+
+    for (name, help, num) in devices.counts() {
+        let opts = new_opt(name, help);
+        let gauge = IntGauge::with_opts(opts).unwrap();
+        gauge.set(num as i64);
+        r.register(Box::new(gauge)).unwrap();
+    }
+
+    let device_vec = IntCounterVec::new(
+        new_opt("device_uptime_min", "Devices"),
+        &["connection", "mac", "friendly_name"],
+    )
+    .unwrap();
+    for dev in devices.online.iter() {
+        device_vec
+            .with_label_values(&["online", &dev.mac, dev.name.as_ref().unwrap_or(&dev.mac)])
+            .inc_by(dev.uptime.as_ref().map_or(0, |u| u.minutes));
+    }
+
+    for dev in devices.offline.iter() {
+        device_vec
+            .with_label_values(&["offline", &dev.mac, dev.name.as_ref().unwrap_or(&dev.mac)])
+            .inc_by(0);
+    }
+    r.register(Box::new(device_vec)).unwrap();
+
+    // Missing when the router has no statistics page
+    if let Some(statistics) = &snapshot.statistics {
+        for (name, help, value) in [
+            (
+                "router_month_download_bytes",
+                "Download traffic this month according to the router",
+                statistics
+                    .month_download
+                    .as_ref()
+                    .and_then(Parsed::bytes)
+                    .map(|b| b as f64),
+            ),
+            (
+                "router_month_upload_bytes",
+                "Upload traffic this month according to the router",
+                statistics
+                    .month_upload
+                    .as_ref()
+                    .and_then(Parsed::bytes)
+                    .map(|b| b as f64),
+            ),
+            (
+                "router_month_duration_seconds",
+                "Connection duration this month according to the router",
+                statistics.month_duration_seconds.map(|s| s as f64),
+            ),
+            (
+                "router_plan_start_day",
+                "Start day of the data plan configured on the router",
+                statistics.plan.start_day.map(|d| d as f64),
+            ),
+            (
+                "router_plan_limit_bytes",
+                "Monthly data limit configured on the router",
+                statistics
+                    .plan
+                    .limit
+                    .as_ref()
+                    .and_then(Parsed::bytes)
+                    .map(|b| b as f64),
+            ),
+            (
+                "router_plan_warning_threshold_percent",
+                "Usage warning threshold configured on the router",
+                statistics.plan.warning_threshold_percent,
+            ),
+        ] {
+            if let Some(value) = value {
+                let gauge = Gauge::with_opts(new_opt(name, help)).unwrap();
+                gauge.set(value);
+                r.register(Box::new(gauge)).unwrap();
+            }
+        }
+    }
+
+    if let Some(totals) = &snapshot.traffic {
+        for (name, help, bytes) in [
+            (
+                "download_bytes_total",
+                "Total download traffic, continued across router counter resets",
+                totals.total.download,
+            ),
+            (
+                "upload_bytes_total",
+                "Total upload traffic, continued across router counter resets",
+                totals.total.upload,
+            ),
+        ] {
+            let counter = IntCounter::with_opts(new_opt(name, help)).unwrap();
+            counter.inc_by(bytes);
+            r.register(Box::new(counter)).unwrap();
+        }
+
+        let restarts = IntCounter::with_opts(new_opt(
+            "router_restarts_total",
+            "Number of detected router restarts or traffic statistic resets",
+        ))
+        .unwrap();
+        restarts.inc_by(totals.restarts);
+        r.register(Box::new(restarts)).unwrap();
+    }
+
+    if let Some(rates) = &snapshot.average_rates {
+        for (name, help, value) in [
+            (
+                "average_download_rate_mbps",
+                "Average download rate since the previous run",
+                rates.download_mbps,
+            ),
+            (
+                "average_upload_rate_mbps",
+                "Average upload rate since the previous run",
+                rates.upload_mbps,
+            ),
+            (
+                "average_rate_interval_seconds",
+                "Time since the previous run the average rates are based on",
+                rates.seconds,
+            ),
+        ] {
+            let gauge = Gauge::with_opts(new_opt(name, help)).unwrap();
+            gauge.set(value);
+            r.register(Box::new(gauge)).unwrap();
+        }
+    }
+
+    if let Some(quota) = &snapshot.quota {
+        let actions = IntCounterVec::new(
+            new_opt("quota_actions_total", "Actions taken by quota rules"),
+            &["action", "threshold", "dry_run"],
+        )
+        .unwrap();
+        for (action, thresholds) in &quota.executed {
+            for (threshold, count) in thresholds {
+                for (dry_run, count) in [("false", count.executed), ("true", count.dry_run)] {
+                    actions
+                        .with_label_values(&[action, threshold, dry_run])
+                        .inc_by(count);
+                }
+            }
+        }
+        r.register(Box::new(actions)).unwrap();
+
+        let disabled = IntGauge::with_opts(new_opt(
+            "mobile_data_disabled_by_quota",
+            "Whether mobile data is currently disabled by a quota rule",
+        ))
+        .unwrap();
+        disabled.set(quota.data_disabled as i64);
+        r.register(Box::new(disabled)).unwrap();
+    }
+
+    if let Some(usage) = &snapshot.usage {
+        let cycle = &usage.cycle;
+        for (name, help, bytes) in [
+            (
+                "day_used_bytes",
+                "Data used today",
+                Some(usage.today.total()),
+            ),
+            (
+                "month_used_bytes",
+                "Data used this calendar month",
+                Some(usage.month.total()),
+            ),
+            (
+                "cycle_used_bytes",
+                "Data used in the current billing cycle",
+                Some(cycle.used.total()),
+            ),
+            (
+                "cycle_quota_bytes",
+                "Data included in the billing cycle",
+                cycle.quota,
+            ),
+            (
+                "cycle_remaining_bytes",
+                "Data remaining in the current billing cycle",
+                cycle.remaining,
+            ),
+            (
+                "cycle_projected_bytes",
+                "Projected data usage at the end of the billing cycle",
+                Some(cycle.projected),
+            ),
+        ] {
+            if let Some(bytes) = bytes {
+                let gauge = IntGauge::with_opts(new_opt(name, help)).unwrap();
+                gauge.set(bytes as i64);
+                r.register(Box::new(gauge)).unwrap();
+            }
+        }
+    }
+
+    for (label, value) in info {
+        if let Some(Parsed {
+            value: numeric_value,
+            unit,
+        }) = &value.parsed
+        {
+            let opts = new_opt(
+                &format!(
+                    "{}_{}",
+                    label.to_ascii_lowercase(),
+                    unit.to_ascii_lowercase()
+                ),
+                &value.label.clone(),
+            );
+            match unit.as_str() {
+                "Mbps" | "Kbps" | "Bps" | "dBm" | "dB" => {
+                    let gauge = Gauge::with_opts(opts).unwrap();
+                    gauge.set(*numeric_value);
+                    r.register(Box::new(gauge)).unwrap();
+                }
+                "MB" | "GB" | "KB" | "B" => {
+                    let counter = Counter::with_opts(opts).unwrap();
+                    counter.inc_by(*numeric_value);
+                    r.register(Box::new(counter)).unwrap();
+                }
+                _ => {
+                    warn!(
+                        "Skipping {:?} because of unknown unit to metric conversion",
+                        info
+                    );
+                }
+            }
+        }
+    }
+
+    r
+}
+
+/// Renders the registry in the prometheus text exposition format.
+pub fn encode_text(registry: &Registry) -> String {
+    let mut buffer = vec![];
+    let encoder = TextEncoder::new();
+    let metric_families = registry.gather();
+    encoder.encode(&metric_families, &mut buffer).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
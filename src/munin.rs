@@ -0,0 +1,223 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{sinks::mqtt::object_id, snapshot::Snapshot};
+
+/// Names of the plugin symlinks, `huawei_<graph>`.
+pub const GRAPHS: [&str; 3] = ["signal", "traffic", "devices"];
+const SYMLINK_PREFIX: &str = "huawei_";
+const ENV_PLUGSTATE: &str = "MUNIN_PLUGSTATE";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub label: String,
+    pub field_type: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Graph {
+    pub name: String,
+    pub title: String,
+    pub vlabel: String,
+    pub args: String,
+    pub fields: Vec<Field>,
+}
+
+impl Graph {
+    /// Output of `<plugin> config`.
+    pub fn config(&self) -> String {
+        let mut out = format!(
+            "graph_title {}\ngraph_vlabel {}\ngraph_category network\n",
+            self.title, self.vlabel
+        );
+        if !self.args.is_empty() {
+            out.push_str(&format!("graph_args {}\n", self.args));
+        }
+        for field in &self.fields {
+            out.push_str(&format!("{}.label {}\n", field.name, field.label));
+            out.push_str(&format!("{}.type {}\n", field.name, field.field_type));
+            if field.field_type == "DERIVE" {
+                out.push_str(&format!("{}.min 0\n", field.name));
+            }
+        }
+        out
+    }
+
+    /// Output for the plugin invocation, `config` or the values without a mode.
+    pub fn output(&self, mode: Option<&str>) -> String {
+        match mode {
+            Some("config") => self.config(),
+            _ => self.values(),
+        }
+    }
+
+    /// Output of `<plugin>`, the values.
+    pub fn values(&self) -> String {
+        self.fields
+            .iter()
+            .map(|f| format!("{}.value {}\n", f.name, f.value))
+            .collect()
+    }
+}
+
+/// The graph name if the binary was invoked through a `huawei_<graph>` symlink.
+pub fn invoked_as(arg0: &str) -> Option<&'static str> {
+    let name = Path::new(arg0).file_name()?.to_str()?;
+    let graph = name.strip_prefix(SYMLINK_PREFIX)?;
+    GRAPHS.iter().find(|g| **g == graph).copied()
+}
+
+pub fn graphs(snapshot: &Snapshot) -> Vec<Graph> {
+    let mut info: Vec<_> = snapshot.info.iter().collect();
+    info.sort_by_key(|(key, _)| key.as_str());
+    let signal = info
+        .iter()
+        .filter_map(|(key, information)| {
+            let parsed = information.parsed.as_ref()?;
+            match parsed.unit.as_str() {
+                "dB" | "dBm" => Some(Field {
+                    name: object_id(key),
+                    label: format!("{} ({})", information.label, parsed.unit),
+                    field_type: "GAUGE".to_string(),
+                    value: parsed.value,
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+
+    // Prefer the totals continued across router restarts when a state file is used
+    let (download, upload) = match &snapshot.traffic {
+        Some(totals) => (Some(totals.total.download), Some(totals.total.upload)),
+        None => (
+            snapshot.info.get("totaldownload").and_then(|i| i.bytes()),
+            snapshot.info.get("totalupload").and_then(|i| i.bytes()),
+        ),
+    };
+    let traffic = [("down", "Download", download), ("up", "Upload", upload)]
+        .iter()
+        .filter_map(|(name, label, bytes)| {
+            Some(Field {
+                name: name.to_string(),
+                label: label.to_string(),
+                field_type: "DERIVE".to_string(),
+                value: (*bytes)? as f64,
+            })
+        })
+        .collect();
+
+    let devices = snapshot
+        .devices
+        .counts()
+        .iter()
+        .map(|(name, help, count)| Field {
+            name: name.to_string(),
+            label: help.trim_start_matches("Number of ").to_string(),
+            field_type: "GAUGE".to_string(),
+            value: *count as f64,
+        })
+        .collect();
+
+    vec![
+        Graph {
+            name: "signal".to_string(),
+            title: "HUAWEI signal quality".to_string(),
+            vlabel: "dB / dBm".to_string(),
+            args: "".to_string(),
+            fields: signal,
+        },
+        Graph {
+            name: "traffic".to_string(),
+            title: "HUAWEI traffic".to_string(),
+            vlabel: "bytes per ${graph_period}".to_string(),
+            args: "--base 1024".to_string(),
+            fields: traffic,
+        },
+        Graph {
+            name: "devices".to_string(),
+            title: "HUAWEI devices".to_string(),
+            vlabel: "devices".to_string(),
+            args: "--lower-limit 0".to_string(),
+            fields: devices,
+        },
+    ]
+}
+
+/// All graphs of one scrape, shared by the plugin names so only one of them logs into the router.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cache {
+    pub time: DateTime<Local>,
+    pub graphs: Vec<Graph>,
+}
+
+impl Cache {
+    fn path() -> PathBuf {
+        env::var_os(ENV_PLUGSTATE)
+            .map_or_else(env::temp_dir, PathBuf::from)
+            .join("huawei-metrics-munin.json")
+    }
+
+    /// The cached graphs, unless they are older than `max_age`.
+    pub fn load(max_age: Duration) -> Option<Cache> {
+        let path = Self::path();
+        let data = fs::read(&path).ok()?;
+        match serde_json::from_slice::<Cache>(&data) {
+            Ok(cache) if Local::now() - cache.time <= max_age => Some(cache),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Ignoring invalid cache {}: {:#}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        // Written to a temporary file first, so concurrent plugins never read half a cache
+        let tmp = path.with_extension("json.tmp");
+        let result = fs::write(&tmp, serde_json::to_vec(self).unwrap())
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(e) = result {
+            warn!("Unable to write cache {}: {:#}", path.display(), e);
+        }
+    }
+
+    pub fn graph(&self, name: &str) -> &Graph {
+        self.graphs.iter().find(|g| g.name == name).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{graphs, invoked_as};
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn plugin_output() {
+        assert_eq!(
+            invoked_as("/etc/munin/plugins/huawei_signal"),
+            Some("signal")
+        );
+        assert_eq!(invoked_as("huawei-metrics"), None);
+        assert_eq!(invoked_as("huawei_unknown"), None);
+
+        let graphs = graphs(&Snapshot::example());
+        assert_eq!(
+            graphs[0].config(),
+            "graph_title HUAWEI signal quality\ngraph_vlabel dB / dBm\ngraph_category network\n\
+            rsrp.label RSRP (dBm)\nrsrp.type GAUGE\nsinr.label SINR (dB)\nsinr.type GAUGE\n"
+        );
+        assert_eq!(graphs[0].values(), "rsrp.value -109\nsinr.value -1\n");
+        assert_eq!(graphs[1].values(), "down.value 2147483648\n");
+        assert!(graphs[2]
+            .config()
+            .contains("online_devices.label online devices\n"));
+    }
+}
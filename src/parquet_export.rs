@@ -0,0 +1,372 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Days, Local, NaiveDate, TimeZone};
+use parquet::{
+    basic::Compression,
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::{parser::parse_message_type, types::Type},
+};
+
+use crate::{history::Recorded, Parsed};
+
+/// Parsed information values other than data volumes, one row per value.
+const SIGNAL_SCHEMA: &str = "message signal {
+    REQUIRED INT64 time (TIMESTAMP(MILLIS,true));
+    REQUIRED BYTE_ARRAY router (STRING);
+    REQUIRED BYTE_ARRAY key (STRING);
+    REQUIRED DOUBLE value;
+    REQUIRED BYTE_ARRAY unit (STRING);
+}";
+
+/// The router's own traffic counters, one row per snapshot.
+const TRAFFIC_SCHEMA: &str = "message traffic {
+    REQUIRED INT64 time (TIMESTAMP(MILLIS,true));
+    REQUIRED BYTE_ARRAY router (STRING);
+    OPTIONAL INT64 download_bytes;
+    OPTIONAL INT64 upload_bytes;
+}";
+
+/// Known devices, one row per device and snapshot.
+const DEVICES_SCHEMA: &str = "message devices {
+    REQUIRED INT64 time (TIMESTAMP(MILLIS,true));
+    REQUIRED BYTE_ARRAY router (STRING);
+    REQUIRED BYTE_ARRAY mac (STRING);
+    OPTIONAL BYTE_ARRAY name (STRING);
+    REQUIRED BOOLEAN online;
+    OPTIONAL BYTE_ARRAY connection (STRING);
+    OPTIONAL INT64 uptime_minutes;
+}";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i64),
+    Double(f64),
+    Text(String),
+    Bool(bool),
+}
+
+/// Rows of one table for the current partition, stored per column.
+struct Table {
+    name: &'static str,
+    schema: Arc<Type>,
+    columns: Vec<Vec<Option<Value>>>,
+}
+
+impl Table {
+    fn new(name: &'static str, schema: &str) -> Self {
+        let schema = parse_message_type(schema).expect("Invalid Parquet schema");
+        Table {
+            name,
+            columns: vec![Vec::new(); schema.get_fields().len()],
+            schema: Arc::new(schema),
+        }
+    }
+
+    fn push(&mut self, row: Vec<Option<Value>>) {
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+    }
+
+    /// Writes the buffered rows to `path` and clears them.
+    fn write(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = SerializedFileWriter::new(
+            File::create(path)?,
+            self.schema.clone(),
+            Arc::new(properties),
+        )?;
+        let mut row_group = writer.next_row_group()?;
+        for values in &mut self.columns {
+            let mut column = row_group.next_column()?.unwrap();
+            write_column(column.untyped(), values)?;
+            column.close()?;
+            values.clear();
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+fn write_column(writer: &mut ColumnWriter, values: &[Option<Value>]) -> Result<(), Box<dyn Error>> {
+    // Only used by the writer for optional columns
+    let levels: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+    let present = values.iter().flatten();
+    match writer {
+        ColumnWriter::Int64ColumnWriter(w) => {
+            let values: Vec<_> = present
+                .filter_map(|v| match v {
+                    Value::Int(i) => Some(*i),
+                    _ => None,
+                })
+                .collect();
+            w.write_batch(&values, Some(&levels), None)?;
+        }
+        ColumnWriter::DoubleColumnWriter(w) => {
+            let values: Vec<_> = present
+                .filter_map(|v| match v {
+                    Value::Double(d) => Some(*d),
+                    _ => None,
+                })
+                .collect();
+            w.write_batch(&values, Some(&levels), None)?;
+        }
+        ColumnWriter::ByteArrayColumnWriter(w) => {
+            let values: Vec<_> = present
+                .filter_map(|v| match v {
+                    Value::Text(t) => Some(ByteArray::from(t.as_str())),
+                    _ => None,
+                })
+                .collect();
+            w.write_batch(&values, Some(&levels), None)?;
+        }
+        ColumnWriter::BoolColumnWriter(w) => {
+            let values: Vec<_> = present
+                .filter_map(|v| match v {
+                    Value::Bool(b) => Some(*b),
+                    _ => None,
+                })
+                .collect();
+            w.write_batch(&values, Some(&levels), None)?;
+        }
+        _ => unreachable!("Column type not used by the schemas"),
+    }
+    Ok(())
+}
+
+/// Where an export starting at `since` begins: partitions are replaced as a whole, so the
+/// partial day `since` falls on is skipped instead of being rewritten with only part of its rows.
+pub fn first_full_day(since: DateTime<Local>) -> DateTime<Local> {
+    let midnight = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+    };
+    let date = since.date_naive();
+    match midnight(date) {
+        Some(start) if start == since => since,
+        _ => date
+            .checked_add_days(Days::new(1))
+            .and_then(midnight)
+            .unwrap_or(since),
+    }
+}
+
+/// Writes recorded snapshots as Parquet files partitioned by local date, as
+/// `<dir>/<table>/date=<YYYY-MM-DD>/data.parquet`. Snapshots have to come oldest first,
+/// a partition is written once the next day starts and replaces an existing file.
+pub struct Exporter {
+    dir: PathBuf,
+    date: Option<NaiveDate>,
+    tables: [Table; 3],
+    files: Vec<PathBuf>,
+}
+
+impl Exporter {
+    pub fn new(dir: &Path) -> Self {
+        Exporter {
+            dir: dir.to_path_buf(),
+            date: None,
+            tables: [
+                Table::new("signal", SIGNAL_SCHEMA),
+                Table::new("traffic", TRAFFIC_SCHEMA),
+                Table::new("devices", DEVICES_SCHEMA),
+            ],
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, recorded: &Recorded) -> Result<(), Box<dyn Error>> {
+        let date = recorded.time.date_naive();
+        if self.date != Some(date) {
+            self.flush()?;
+            self.date = Some(date);
+        }
+        let time = || Some(Value::Int(recorded.time.timestamp_millis()));
+        let router = || Some(Value::Text(recorded.router.clone()));
+        let [signal, traffic, devices] = &mut self.tables;
+
+        for info in &recorded.info {
+            let parsed = match (info.number, &info.unit) {
+                (Some(value), Some(unit)) => Parsed {
+                    value,
+                    unit: unit.clone(),
+                },
+                _ => continue,
+            };
+            // Volumes are in the traffic table
+            if parsed.bytes().is_some() {
+                continue;
+            }
+            signal.push(vec![
+                time(),
+                router(),
+                Some(Value::Text(info.key.clone())),
+                Some(Value::Double(parsed.value)),
+                Some(Value::Text(parsed.unit)),
+            ]);
+        }
+        traffic.push(vec![
+            time(),
+            router(),
+            recorded.download_bytes.map(|b| Value::Int(b as i64)),
+            recorded.upload_bytes.map(|b| Value::Int(b as i64)),
+        ]);
+        for device in &recorded.devices {
+            devices.push(vec![
+                time(),
+                router(),
+                Some(Value::Text(device.mac.clone())),
+                device.name.clone().map(Value::Text),
+                Some(Value::Bool(device.online)),
+                device.connection.clone().map(Value::Text),
+                device.uptime_minutes.map(Value::Int),
+            ]);
+        }
+        Ok(())
+    }
+
+    /// Writes the partition of the current date.
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let date = match self.date {
+            Some(date) => date,
+            None => return Ok(()),
+        };
+        for table in &mut self.tables {
+            if table.columns[0].is_empty() {
+                continue;
+            }
+            let partition = self
+                .dir
+                .join(table.name)
+                .join(format!("date={}", date.format("%Y-%m-%d")));
+            fs::create_dir_all(&partition)?;
+            let path = partition.join("data.parquet");
+            debug!("Writing {}", path.display());
+            table.write(&path)?;
+            self.files.push(path);
+        }
+        Ok(())
+    }
+
+    /// Writes the last partition, returns all files written.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        self.flush()?;
+        Ok(self.files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use chrono::{Duration, Local, TimeZone};
+    use parquet::{file::reader::SerializedFileReader, record::Field};
+
+    use super::{first_full_day, Exporter};
+    use crate::{history::Recorded, snapshot::Snapshot, testing::temp_dir};
+
+    fn rows(path: &std::path::Path) -> Vec<Vec<(String, Field)>> {
+        let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+        reader
+            .into_iter()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(name, field)| (name.clone(), field.clone()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partitioned_tables() {
+        let temp = temp_dir("parquet");
+        let dir = temp.path();
+        let mut snapshot = Snapshot::example();
+        let mut exporter = Exporter::new(dir);
+        exporter.add(&Recorded::from(&snapshot)).unwrap();
+        snapshot.metadata.time += Duration::days(1);
+        exporter.add(&Recorded::from(&snapshot)).unwrap();
+        let files = exporter.finish().unwrap();
+
+        let names: Vec<_> = files
+            .iter()
+            .map(|p| p.strip_prefix(dir).unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "signal/date=2021-06-01/data.parquet",
+                "traffic/date=2021-06-01/data.parquet",
+                "devices/date=2021-06-01/data.parquet",
+                "signal/date=2021-06-02/data.parquet",
+                "traffic/date=2021-06-02/data.parquet",
+                "devices/date=2021-06-02/data.parquet",
+            ]
+        );
+
+        let time = Field::TimestampMillis(Snapshot::example().metadata.time.timestamp_millis());
+        let router = Field::Str("192.168.8.1".to_string());
+        let signal = rows(&files[0]);
+        // totaldownload is a volume and only in the traffic table
+        assert_eq!(signal.len(), 2);
+        assert_eq!(
+            signal[1],
+            [
+                ("time".to_string(), time.clone()),
+                ("router".to_string(), router.clone()),
+                ("key".to_string(), Field::Str("sinr".to_string())),
+                ("value".to_string(), Field::Double(-1.0)),
+                ("unit".to_string(), Field::Str("dB".to_string())),
+            ]
+        );
+        assert_eq!(
+            rows(&files[1]),
+            [[
+                ("time".to_string(), time.clone()),
+                ("router".to_string(), router.clone()),
+                (
+                    "download_bytes".to_string(),
+                    Field::Long(2048 * 1024 * 1024)
+                ),
+                ("upload_bytes".to_string(), Field::Null),
+            ]]
+        );
+        let devices = rows(&files[2]);
+        assert_eq!(devices.len(), 2);
+        assert_eq!(
+            devices[1],
+            [
+                ("time".to_string(), time),
+                ("router".to_string(), router),
+                (
+                    "mac".to_string(),
+                    Field::Str("AA:BB:CC:DD:EE:02".to_string())
+                ),
+                ("name".to_string(), Field::Null),
+                ("online".to_string(), Field::Bool(false)),
+                ("connection".to_string(), Field::Null),
+                ("uptime_minutes".to_string(), Field::Null),
+            ]
+        );
+    }
+
+    #[test]
+    fn since_skips_partial_day() {
+        let noon = Snapshot::example().metadata.time;
+        let midnight = Local.with_ymd_and_hms(2021, 6, 2, 0, 0, 0).unwrap();
+        assert_eq!(first_full_day(noon), midnight);
+        assert_eq!(first_full_day(midnight), midnight);
+    }
+}
@@ -0,0 +1,89 @@
+/// Minimal protobuf encoder for the handful of messages the push sinks send.
+#[derive(Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LENGTH_DELIMITED: u8 = 2;
+
+impl Writer {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint(u64::from(field) << 3 | u64::from(wire_type));
+    }
+
+    pub fn int64(&mut self, field: u32, value: i64) {
+        self.key(field, VARINT);
+        self.varint(value as u64);
+    }
+
+    pub fn bool(&mut self, field: u32, value: bool) {
+        self.key(field, VARINT);
+        self.varint(value as u64);
+    }
+
+    pub fn fixed64(&mut self, field: u32, value: u64) {
+        self.key(field, FIXED64);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn double(&mut self, field: u32, value: f64) {
+        self.key(field, FIXED64);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.key(field, LENGTH_DELIMITED);
+        self.varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    /// Encodes an embedded message written by `f`.
+    pub fn message(&mut self, field: u32, f: impl FnOnce(&mut Writer)) {
+        let mut inner = Writer::default();
+        f(&mut inner);
+        self.bytes(field, &inner.buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Writer;
+
+    #[test]
+    fn encoding() {
+        let mut w = Writer::default();
+        w.int64(1, 150);
+        w.string(2, "testing");
+        w.message(3, |m| m.int64(1, 150));
+        w.int64(4, -1);
+        assert_eq!(
+            w.into_bytes(),
+            [
+                &[0x08, 0x96, 0x01][..],
+                &[0x12, 0x07],
+                b"testing",
+                &[0x1a, 0x03, 0x08, 0x96, 0x01],
+                &[0x20, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    process::Command,
+    str::FromStr,
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use fantoccini::{Client, Locator};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+use crate::usage::CycleUsage;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleAction {
+    Notify,
+    DisableData,
+}
+
+/// Action to take once `percent` of the billing cycle's quota is used, e.g. `80:notify`.
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaRule {
+    pub percent: f64,
+    pub action: RuleAction,
+}
+
+impl FromStr for QuotaRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (percent, action) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected <percent>:<action>, got {}", s))?;
+        let percent = percent
+            .trim_end_matches('%')
+            .parse()
+            .map_err(|_| format!("Invalid percentage in quota rule {}", s))?;
+        let action = match action {
+            "notify" => RuleAction::Notify,
+            "disable-data" => RuleAction::DisableData,
+            a => return Err(format!("Unknown quota rule action {}", a)),
+        };
+        Ok(QuotaRule { percent, action })
+    }
+}
+
+impl fmt::Display for QuotaRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            RuleAction::Notify => "notify",
+            RuleAction::DisableData => "disable-data",
+        };
+        write!(f, "{}:{}", self.percent, action)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "action")]
+pub enum QuotaAction {
+    #[serde(rename = "notify")]
+    Notify { threshold: String },
+    #[serde(rename = "disable-data")]
+    DisableData { threshold: String },
+    /// Undoes a previous `DisableData` once a new billing cycle has started.
+    #[serde(rename = "enable-data")]
+    EnableData,
+}
+
+impl QuotaAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuotaAction::Notify { .. } => "notify",
+            QuotaAction::DisableData { .. } => "disable-data",
+            QuotaAction::EnableData => "enable-data",
+        }
+    }
+
+    pub fn threshold(&self) -> &str {
+        match self {
+            QuotaAction::Notify { threshold } | QuotaAction::DisableData { threshold } => threshold,
+            QuotaAction::EnableData => "cycle_start",
+        }
+    }
+}
+
+/// Remembers which rules already fired in the current billing cycle.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct QuotaEnforcer {
+    cycle_start: Option<NaiveDate>,
+    fired: BTreeSet<String>,
+    /// Rules only logged in dry run mode, so they are not logged again every run.
+    #[serde(default)]
+    dry_run_fired: BTreeSet<String>,
+    pub data_disabled: bool,
+    /// Data was disabled in a previous cycle and is to be enabled until that succeeded.
+    #[serde(default)]
+    enable_pending: bool,
+    /// Executed actions by action and threshold.
+    pub executed: BTreeMap<String, BTreeMap<String, ActionCount>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct ActionCount {
+    pub executed: u64,
+    pub dry_run: u64,
+}
+
+impl QuotaEnforcer {
+    pub fn plan(
+        &mut self,
+        rules: &[QuotaRule],
+        usage: &CycleUsage,
+        dry_run: bool,
+    ) -> Vec<QuotaAction> {
+        let mut actions = Vec::new();
+        let done = |enforcer: &Self, threshold: &str| {
+            enforcer.fired.contains(threshold)
+                || (dry_run && enforcer.dry_run_fired.contains(threshold))
+        };
+
+        if self.cycle_start != Some(usage.start) {
+            debug!("New billing cycle starting {}", usage.start);
+            self.cycle_start = Some(usage.start);
+            self.fired.clear();
+            self.dry_run_fired.clear();
+            self.enable_pending = self.data_disabled;
+        }
+        if self.enable_pending && !done(self, QuotaAction::EnableData.threshold()) {
+            actions.push(QuotaAction::EnableData);
+        }
+
+        let quota = match usage.quota {
+            Some(quota) => quota,
+            None => {
+                if !rules.is_empty() {
+                    warn!("Quota rules are configured without a quota, ignoring them");
+                }
+                return actions;
+            }
+        };
+        let percent = usage.used.total() as f64 * 100f64 / quota as f64;
+
+        for rule in rules {
+            let threshold = rule.to_string();
+            if percent >= rule.percent && !done(self, &threshold) {
+                actions.push(match rule.action {
+                    RuleAction::Notify => QuotaAction::Notify { threshold },
+                    RuleAction::DisableData => QuotaAction::DisableData { threshold },
+                });
+            }
+        }
+        actions
+    }
+
+    /// Marks `action` as done, dry runs are only counted and not repeated in the cycle.
+    pub fn record(&mut self, action: &QuotaAction, dry_run: bool) {
+        let count = self
+            .executed
+            .entry(action.name().to_string())
+            .or_default()
+            .entry(action.threshold().to_string())
+            .or_default();
+        if dry_run {
+            count.dry_run += 1;
+            self.dry_run_fired.insert(action.threshold().to_string());
+            return;
+        }
+        count.executed += 1;
+        match action {
+            QuotaAction::Notify { threshold } => {
+                self.fired.insert(threshold.clone());
+            }
+            QuotaAction::DisableData { threshold } => {
+                self.fired.insert(threshold.clone());
+                self.data_disabled = true;
+            }
+            QuotaAction::EnableData => {
+                self.data_disabled = false;
+                self.enable_pending = false;
+            }
+        }
+    }
+}
+
+/// Outcome of applying the quota rules in one run.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuotaStatus {
+    pub data_disabled: bool,
+    pub dry_run: bool,
+    pub actions: Vec<QuotaAction>,
+    pub executed: BTreeMap<String, BTreeMap<String, ActionCount>>,
+}
+
+/// Runs `command` with a shell, passing the quota situation in the environment.
+pub fn notify(command: &str, threshold: &str, usage: &CycleUsage) -> Result<(), String> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("HUAWEI_QUOTA_THRESHOLD", threshold)
+        .env("HUAWEI_QUOTA_USED_BYTES", usage.used.total().to_string())
+        .env(
+            "HUAWEI_QUOTA_BYTES",
+            usage.quota.unwrap_or_default().to_string(),
+        )
+        .env("HUAWEI_QUOTA_CYCLE_END", usage.end.to_string())
+        .status()
+        .map_err(|e| format!("Unable to run notify command: {:#}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Notify command failed with {}", status))
+    }
+}
+
+/// Whether the mobile data switch is on, from the class of its element.
+fn switch_state(class: Option<&str>) -> Result<bool, String> {
+    let class = class.ok_or("Mobile data switch has no class")?;
+    let classes: Vec<_> = class.split_whitespace().collect();
+    if classes.contains(&"switch_on") {
+        Ok(true)
+    } else if classes.contains(&"switch_off") {
+        Ok(false)
+    } else {
+        Err(format!("Unknown mobile data switch state {:?}", class))
+    }
+}
+
+async fn read_switch(c: &mut Client) -> Result<bool, String> {
+    let mut switch = c
+        .find(Locator::Id("mobile_connect_switch"))
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    let class = switch.attr("class").await.map_err(|e| format!("{:#}", e))?;
+    switch_state(class.as_deref())
+}
+
+/// Flips the mobile data switch on the router's mobile network settings page, failing unless
+/// the switch ends up in the requested state.
+pub async fn set_mobile_data(
+    c: &mut Client,
+    router_host: &str,
+    enabled: bool,
+) -> Result<(), String> {
+    let state = |enabled| if enabled { "on" } else { "off" };
+    debug!("Navigating to mobile connection page");
+    c.goto(&format!(
+        "http://{}/html/content.html#mobileconnection",
+        router_host
+    ))
+    .await
+    .map_err(|e| format!("{:#}", e))?;
+    c.wait_for_find(Locator::Id("mobileconnection_page"))
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    sleep(Duration::from_millis(2000)).await;
+
+    if read_switch(c).await? == enabled {
+        info!("Mobile data already {}", state(enabled));
+        return Ok(());
+    }
+
+    c.find(Locator::Id("mobile_connect_switch"))
+        .await
+        .map_err(|e| format!("{:#}", e))?
+        .click()
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    debug!("Clicked mobile data switch");
+    sleep(Duration::from_millis(2000)).await;
+
+    // A confirmation dialog or a rejected change leaves the switch as it was
+    if read_switch(c).await? != enabled {
+        return Err(format!(
+            "Mobile data is still {} after clicking the switch",
+            state(!enabled)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{switch_state, QuotaAction, QuotaEnforcer, QuotaRule};
+    use crate::usage::{CycleUsage, Traffic};
+
+    fn cycle(start_month: u32, used: u64) -> CycleUsage {
+        CycleUsage {
+            start: NaiveDate::from_ymd_opt(2021, start_month, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2021, start_month + 1, 1).unwrap(),
+            used: Traffic {
+                download: used,
+                upload: 0,
+            },
+            quota: Some(100),
+            remaining: Some(100u64.saturating_sub(used)),
+            projected: used,
+        }
+    }
+
+    #[test]
+    fn rules_fire_once_per_cycle() {
+        let rules: Vec<QuotaRule> = ["80:notify", "100:disable-data"]
+            .iter()
+            .map(|r| r.parse().unwrap())
+            .collect();
+        let mut enforcer = QuotaEnforcer::default();
+
+        let mut run = |usage: CycleUsage| {
+            let actions = enforcer.plan(&rules, &usage, false);
+            for action in &actions {
+                enforcer.record(action, false);
+            }
+            actions.iter().map(|a| a.name()).collect::<Vec<_>>()
+        };
+
+        assert!(run(cycle(5, 50)).is_empty());
+        assert_eq!(run(cycle(5, 90)), ["notify"]);
+        assert_eq!(run(cycle(5, 100)), ["disable-data"]);
+        assert!(run(cycle(5, 120)).is_empty());
+        assert_eq!(run(cycle(6, 0)), ["enable-data"]);
+        assert!(matches!(
+            enforcer.plan(&rules, &cycle(6, 85), false)[..],
+            [QuotaAction::Notify { .. }]
+        ));
+    }
+
+    #[test]
+    fn enable_data_retried_until_done() {
+        let rules: Vec<QuotaRule> = vec!["100:disable-data".parse().unwrap()];
+        let mut enforcer = QuotaEnforcer::default();
+        for action in enforcer.plan(&rules, &cycle(5, 100), false) {
+            enforcer.record(&action, false);
+        }
+        assert!(enforcer.data_disabled);
+
+        // Switching data back on failed, so nothing is recorded
+        let names =
+            |actions: Vec<QuotaAction>| actions.iter().map(|a| a.name()).collect::<Vec<_>>();
+        assert_eq!(
+            names(enforcer.plan(&rules, &cycle(6, 0), false)),
+            ["enable-data"]
+        );
+        let actions = enforcer.plan(&rules, &cycle(6, 1), false);
+        assert_eq!(names(actions.clone()), ["enable-data"]);
+        enforcer.record(&actions[0], false);
+        assert!(!enforcer.data_disabled);
+        assert!(enforcer.plan(&rules, &cycle(6, 2), false).is_empty());
+    }
+
+    #[test]
+    fn dry_run_logged_once_per_cycle() {
+        let rules: Vec<QuotaRule> = vec!["80:notify".parse().unwrap()];
+        let mut enforcer = QuotaEnforcer::default();
+        let actions = enforcer.plan(&rules, &cycle(5, 90), true);
+        assert_eq!(actions.len(), 1);
+        enforcer.record(&actions[0], true);
+        assert!(enforcer.plan(&rules, &cycle(5, 95), true).is_empty());
+        // Still executed once dry run mode is turned off
+        assert_eq!(enforcer.plan(&rules, &cycle(5, 95), false).len(), 1);
+        assert_eq!(enforcer.plan(&rules, &cycle(6, 90), true).len(), 1);
+        assert_eq!(enforcer.executed["notify"]["80:notify"].dry_run, 1);
+    }
+
+    #[test]
+    fn switch_classes() {
+        assert_eq!(switch_state(Some("switch switch_on")), Ok(true));
+        assert_eq!(switch_state(Some("switch_off")), Ok(false));
+        assert!(switch_state(Some("switch")).is_err());
+        assert!(switch_state(None).is_err());
+    }
+}
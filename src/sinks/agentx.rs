@@ -0,0 +1,550 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+    time::sleep,
+};
+
+use super::{Sink, SinkError};
+use crate::{
+    snapshot::Snapshot,
+    snmp::{self, Value, BASE_OID},
+};
+
+const VERSION: u8 = 1;
+const NETWORK_BYTE_ORDER: u8 = 0x10;
+
+const OPEN: u8 = 1;
+const CLOSE: u8 = 2;
+const REGISTER: u8 = 3;
+const GET: u8 = 5;
+const GET_NEXT: u8 = 6;
+const GET_BULK: u8 = 7;
+const TEST_SET: u8 = 8;
+const COMMIT_SET: u8 = 9;
+const UNDO_SET: u8 = 10;
+const CLEANUP_SET: u8 = 11;
+const RESPONSE: u8 = 18;
+
+const NOT_WRITABLE: u16 = 17;
+
+const INTEGER: u16 = 2;
+const OCTET_STRING: u16 = 4;
+const COUNTER32: u16 = 65;
+const GAUGE32: u16 = 66;
+const COUNTER64: u16 = 70;
+const NO_SUCH_OBJECT: u16 = 128;
+const END_OF_MIB_VIEW: u16 = 130;
+
+type Values = Arc<RwLock<BTreeMap<Vec<u32>, Value>>>;
+
+#[derive(Debug, Deserialize)]
+pub struct AgentxConfig {
+    /// Master agent socket, a unix socket path or `tcp:<host>:<port>`.
+    #[serde(default = "AgentxConfig::default_master")]
+    pub master: String,
+    /// Seconds to wait before reconnecting to the master agent.
+    #[serde(default = "AgentxConfig::default_reconnect_secs")]
+    pub reconnect_secs: u64,
+}
+
+impl AgentxConfig {
+    fn default_master() -> String {
+        "/var/agentx/master".to_string()
+    }
+
+    fn default_reconnect_secs() -> u64 {
+        10
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Header {
+    pdu_type: u8,
+    flags: u8,
+    session_id: u32,
+    transaction_id: u32,
+    packet_id: u32,
+}
+
+/// Reads the fields of a PDU payload in the byte order announced by its header.
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SinkError> {
+        if self.data.len() < n {
+            return Err("Truncated AgentX PDU".into());
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, SinkError> {
+        let b = [self.take(1)?[0], self.take(1)?[0]];
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&mut self) -> Result<u32, SinkError> {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    /// An object identifier and its include flag.
+    fn oid(&mut self) -> Result<(Vec<u32>, bool), SinkError> {
+        let head = self.take(4)?;
+        let (n_subid, prefix, include) = (head[0], head[1], head[2] != 0);
+        let mut oid = Vec::new();
+        if prefix != 0 {
+            oid.extend_from_slice(&[1, 3, 6, 1, u32::from(prefix)]);
+        }
+        for _ in 0..n_subid {
+            oid.push(self.u32()?);
+        }
+        Ok((oid, include))
+    }
+}
+
+/// Builds PDU payloads, always in network byte order.
+#[derive(Default)]
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u16(&mut self, v: u16) {
+        self.data.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn oid(&mut self, oid: &[u32]) {
+        self.data.extend_from_slice(&[oid.len() as u8, 0, 0, 0]);
+        for sub_id in oid {
+            self.u32(*sub_id);
+        }
+    }
+
+    fn octet_string(&mut self, s: &[u8]) {
+        self.u32(s.len() as u32);
+        self.data.extend_from_slice(s);
+        let padding = (4 - s.len() % 4) % 4;
+        self.data.extend(std::iter::repeat_n(0, padding));
+    }
+
+    fn varbind(&mut self, oid: &[u32], value: Option<&Value>, empty: u16) {
+        let value_type = match value {
+            Some(Value::String(_)) => OCTET_STRING,
+            Some(Value::Integer(_)) => INTEGER,
+            Some(Value::Gauge(_)) => GAUGE32,
+            Some(Value::Counter32(_)) => COUNTER32,
+            Some(Value::Counter64(_)) => COUNTER64,
+            None => empty,
+        };
+        self.u16(value_type);
+        self.u16(0);
+        self.oid(oid);
+        match value {
+            Some(Value::String(s)) => self.octet_string(s.as_bytes()),
+            Some(Value::Integer(i)) => self.u32(*i as u32),
+            Some(Value::Gauge(v)) | Some(Value::Counter32(v)) => self.u32(*v),
+            Some(Value::Counter64(v)) => self.data.extend_from_slice(&v.to_be_bytes()),
+            None => {}
+        }
+    }
+
+    fn pdu(self, pdu_type: u8, session_id: u32, transaction_id: u32, packet_id: u32) -> Vec<u8> {
+        let mut pdu = vec![VERSION, pdu_type, NETWORK_BYTE_ORDER, 0];
+        for v in [
+            session_id,
+            transaction_id,
+            packet_id,
+            self.data.len() as u32,
+        ] {
+            pdu.extend_from_slice(&v.to_be_bytes());
+        }
+        pdu.extend_from_slice(&self.data);
+        pdu
+    }
+}
+
+async fn read_pdu<S: AsyncRead + Unpin>(stream: &mut S) -> Result<(Header, Vec<u8>), SinkError> {
+    let mut header = [0; 20];
+    stream.read_exact(&mut header).await?;
+    let big_endian = header[2] & NETWORK_BYTE_ORDER != 0;
+    let mut reader = Reader {
+        data: &header[4..],
+        big_endian,
+    };
+    let parsed = Header {
+        pdu_type: header[1],
+        flags: header[2],
+        session_id: reader.u32()?,
+        transaction_id: reader.u32()?,
+        packet_id: reader.u32()?,
+    };
+    let mut payload = vec![0; reader.u32()? as usize];
+    stream.read_exact(&mut payload).await?;
+    Ok((parsed, payload))
+}
+
+/// Payload of a response PDU: sysUpTime, error, index and the varbinds.
+fn response(error: u16, index: u16, varbinds: Writer) -> Writer {
+    let mut w = Writer::default();
+    w.u32(0);
+    w.u16(error);
+    w.u16(index);
+    w.data.extend_from_slice(&varbinds.data);
+    w
+}
+
+/// The first value after `start` (or at it when `include` is set) and before `end`.
+fn next<'a>(
+    values: &'a BTreeMap<Vec<u32>, Value>,
+    start: &[u32],
+    include: bool,
+    end: &[u32],
+) -> Option<(&'a Vec<u32>, &'a Value)> {
+    values
+        .range(start.to_vec()..)
+        .find(|(oid, _)| include || oid.as_slice() != start)
+        .filter(|(oid, _)| end.is_empty() || oid.as_slice() < end)
+}
+
+/// Answers a Get, GetNext or GetBulk request.
+fn answer(
+    header: &Header,
+    payload: &[u8],
+    values: &BTreeMap<Vec<u32>, Value>,
+) -> Result<Writer, SinkError> {
+    let mut reader = Reader {
+        data: payload,
+        big_endian: header.flags & NETWORK_BYTE_ORDER != 0,
+    };
+    let (non_repeaters, max_repetitions) = if header.pdu_type == GET_BULK {
+        (reader.u16()? as usize, reader.u16()? as usize)
+    } else {
+        (0, 0)
+    };
+    let mut ranges = Vec::new();
+    while !reader.data.is_empty() {
+        let (start, include) = reader.oid()?;
+        let (end, _) = reader.oid()?;
+        ranges.push((start, include, end));
+    }
+
+    let mut varbinds = Writer::default();
+    if header.pdu_type == GET {
+        for (start, _, _) in &ranges {
+            varbinds.varbind(start, values.get(start), NO_SUCH_OBJECT);
+        }
+        return Ok(response(0, 0, varbinds));
+    }
+    let non_repeaters = if header.pdu_type == GET_BULK {
+        non_repeaters.min(ranges.len())
+    } else {
+        ranges.len()
+    };
+    let (singles, repeaters) = ranges.split_at(non_repeaters);
+    for (start, include, end) in singles {
+        let found = next(values, start, *include, end);
+        varbinds.varbind(
+            found.map_or(start, |(oid, _)| oid),
+            found.map(|(_, v)| v),
+            END_OF_MIB_VIEW,
+        );
+    }
+    // Repetitions are rows of one value per repeater, a repeater past its range keeps
+    // answering endOfMibView so the master agent can match values to OIDs by position
+    let mut cursors: Vec<_> = repeaters
+        .iter()
+        .map(|(start, include, end)| (start.clone(), *include, end))
+        .collect();
+    for _ in 0..max_repetitions {
+        for (start, include, end) in &mut cursors {
+            match next(values, start, *include, end) {
+                Some((oid, value)) => {
+                    varbinds.varbind(oid, Some(value), END_OF_MIB_VIEW);
+                    *start = oid.clone();
+                    *include = false;
+                }
+                None => varbinds.varbind(start, None, END_OF_MIB_VIEW),
+            }
+        }
+    }
+    Ok(response(0, 0, varbinds))
+}
+
+/// Opens a session, registers the subtree and answers requests until the connection is lost.
+async fn session<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    values: &Values,
+) -> Result<(), SinkError> {
+    let mut packet_id = 1;
+    let mut open = Writer::default();
+    open.data.extend_from_slice(&[0, 0, 0, 0]);
+    open.oid(&[]);
+    open.octet_string(concat!("huawei-metrics ", env!("CARGO_PKG_VERSION")).as_bytes());
+    stream.write_all(&open.pdu(OPEN, 0, 0, packet_id)).await?;
+    let (header, payload) = read_pdu(&mut stream).await?;
+    let session_id = header.session_id;
+    check_response(&header, &payload, "Open")?;
+
+    packet_id += 1;
+    let mut register = Writer::default();
+    register.data.extend_from_slice(&[0, 127, 0, 0]);
+    register.oid(&BASE_OID);
+    stream
+        .write_all(&register.pdu(REGISTER, session_id, 0, packet_id))
+        .await?;
+    let (header, payload) = read_pdu(&mut stream).await?;
+    check_response(&header, &payload, "Register")?;
+    info!("Registered AgentX subtree {:?}", BASE_OID);
+
+    loop {
+        let (header, payload) = read_pdu(&mut stream).await?;
+        let reply = match header.pdu_type {
+            GET | GET_NEXT | GET_BULK => answer(&header, &payload, &*values.read().await)?,
+            TEST_SET => response(NOT_WRITABLE, 1, Writer::default()),
+            COMMIT_SET | UNDO_SET => response(0, 0, Writer::default()),
+            CLEANUP_SET | RESPONSE => continue,
+            CLOSE => return Err("Master agent closed the session".into()),
+            other => {
+                debug!("Ignoring AgentX PDU of type {}", other);
+                continue;
+            }
+        };
+        stream
+            .write_all(&reply.pdu(
+                RESPONSE,
+                header.session_id,
+                header.transaction_id,
+                header.packet_id,
+            ))
+            .await?;
+    }
+}
+
+fn check_response(header: &Header, payload: &[u8], request: &str) -> Result<(), SinkError> {
+    let mut reader = Reader {
+        data: payload,
+        big_endian: header.flags & NETWORK_BYTE_ORDER != 0,
+    };
+    reader.u32()?;
+    match (header.pdu_type, reader.u16()?) {
+        (RESPONSE, 0) => Ok(()),
+        (pdu_type, error) => Err(format!(
+            "AgentX {} failed (PDU type {}, error {})",
+            request, pdu_type, error
+        )
+        .into()),
+    }
+}
+
+async fn connect(master: &str, values: &Values) -> Result<(), SinkError> {
+    match master.strip_prefix("tcp:") {
+        Some(address) => session(TcpStream::connect(address).await?, values).await,
+        #[cfg(unix)]
+        None => session(tokio::net::UnixStream::connect(master).await?, values).await,
+        #[cfg(not(unix))]
+        None => Err("Unix sockets are not supported on this platform".into()),
+    }
+}
+
+/// Serves the latest snapshot to an SNMP master agent as AgentX subagent.
+pub struct AgentxSink {
+    config: AgentxConfig,
+    values: Values,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AgentxSink {
+    pub fn new(config: AgentxConfig) -> Self {
+        AgentxSink {
+            config,
+            values: Values::default(),
+            task: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for AgentxSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        *self.values.write().await = snmp::values(snapshot);
+
+        let mut task = self.task.lock().await;
+        if task.is_none() {
+            let master = self.config.master.clone();
+            let reconnect = Duration::from_secs(self.config.reconnect_secs);
+            let values = self.values.clone();
+            *task = Some(tokio::spawn(async move {
+                loop {
+                    if let Err(e) = connect(&master, &values).await {
+                        warn!("AgentX session with {} ended: {:#}", master, e);
+                    }
+                    sleep(reconnect).await;
+                }
+            }));
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use super::*;
+    use crate::snapshot::Snapshot;
+
+    async fn reply(stream: &mut TcpStream, header: &Header, session_id: u32) {
+        stream
+            .write_all(&response(0, 0, Writer::default()).pdu(
+                RESPONSE,
+                session_id,
+                header.transaction_id,
+                header.packet_id,
+            ))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn subagent() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = AgentxConfig {
+            master: format!("tcp:{}", listener.local_addr().unwrap()),
+            reconnect_secs: 1,
+        };
+        let sink = AgentxSink::new(config);
+        sink.send(&Snapshot::example()).await.unwrap();
+
+        let (mut master, _) = listener.accept().await.unwrap();
+        let (open, _) = read_pdu(&mut master).await.unwrap();
+        assert_eq!(open.pdu_type, OPEN);
+        reply(&mut master, &open, 42).await;
+        let (register, payload) = read_pdu(&mut master).await.unwrap();
+        assert_eq!((register.pdu_type, register.session_id), (REGISTER, 42));
+        let mut reader = Reader {
+            data: &payload[4..],
+            big_endian: true,
+        };
+        assert_eq!(reader.oid().unwrap().0, BASE_OID);
+        reply(&mut master, &register, 42).await;
+
+        // GetNext of the subtree root, using the 1.3.6.1.<prefix> compression, and of SINR
+        let mut request = Writer::default();
+        request.data.extend_from_slice(&[2, 4, 0, 0]);
+        request.u32(1);
+        request.u32(BASE_OID[6]);
+        request.oid(&[]);
+        let sinr = [&BASE_OID[..], &[1, 13, 0]].concat();
+        request.oid(&sinr);
+        request.oid(&[]);
+        master
+            .write_all(&request.pdu(GET_NEXT, 42, 7, 8))
+            .await
+            .unwrap();
+        let (header, payload) = read_pdu(&mut master).await.unwrap();
+        assert_eq!((header.pdu_type, header.transaction_id), (RESPONSE, 7));
+
+        let mut reader = Reader {
+            data: &payload[8..],
+            big_endian: true,
+        };
+        // The first object is the router host, the one after SINR the download counter
+        assert_eq!(reader.u16().unwrap(), OCTET_STRING);
+        reader.u16().unwrap();
+        assert_eq!(
+            reader.oid().unwrap().0,
+            [&BASE_OID[..], &[1, 1, 0]].concat()
+        );
+        let length = reader.u32().unwrap() as usize;
+        assert_eq!(reader.take(length).unwrap(), b"192.168.8.1");
+        reader.take(1).unwrap();
+        assert_eq!(reader.u16().unwrap(), COUNTER64);
+        reader.u16().unwrap();
+        assert_eq!(
+            reader.oid().unwrap().0,
+            [&BASE_OID[..], &[1, 20, 0]].concat()
+        );
+
+        sink.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn bulk_rows() {
+        let oid = |sub_id| [&BASE_OID[..], &[1, sub_id, 0]].concat();
+        let values: BTreeMap<_, _> = (1..=3).map(|i| (oid(i), Value::Gauge(i))).collect();
+        let header = Header {
+            pdu_type: GET_BULK,
+            flags: NETWORK_BYTE_ORDER,
+            session_id: 42,
+            transaction_id: 7,
+            packet_id: 8,
+        };
+        // One non-repeater and two repeaters starting at the first and the last object
+        let mut request = Writer::default();
+        request.data.extend_from_slice(&[0, 1, 0, 3]);
+        for start in [oid(2), oid(1), oid(3)] {
+            request.oid(&start);
+            request.oid(&[]);
+        }
+        let answer = answer(&header, &request.data, &values).unwrap();
+
+        let mut reader = Reader {
+            data: &answer.data[8..],
+            big_endian: true,
+        };
+        let mut varbinds = Vec::new();
+        while !reader.data.is_empty() {
+            let value_type = reader.u16().unwrap();
+            reader.u16().unwrap();
+            let (name, _) = reader.oid().unwrap();
+            let value = match value_type {
+                GAUGE32 => Some(reader.u32().unwrap()),
+                _ => None,
+            };
+            varbinds.push((name, value));
+        }
+        assert_eq!(
+            varbinds,
+            [
+                (oid(3), Some(3)),
+                (oid(2), Some(2)),
+                (oid(3), None),
+                (oid(3), Some(3)),
+                (oid(3), None),
+                (oid(3), None),
+                (oid(3), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Timelike};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::{Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+/// Layout of the archive below `dir`, one file per snapshot.
+const PATH_FORMAT: &str = "%Y/%m/%d/%H%M%S";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "json.gz",
+            Compression::Zstd => "json.zst",
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(data, 19),
+        }
+    }
+}
+
+/// Which snapshots are kept, thinning out older ones.
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Retention {
+    /// Every snapshot is kept for this many days.
+    #[serde(default = "Retention::default_keep_all_days")]
+    pub keep_all_days: u32,
+    /// After that, the first snapshot of every hour is kept for this many days.
+    #[serde(default = "Retention::default_keep_hourly_days")]
+    pub keep_hourly_days: u32,
+    /// After that, the first snapshot of every day is kept, forever if not set.
+    pub keep_daily_days: Option<u32>,
+}
+
+impl Retention {
+    fn default_keep_all_days() -> u32 {
+        7
+    }
+
+    fn default_keep_hourly_days() -> u32 {
+        90
+    }
+
+    /// Files of `archive` which are no longer kept at `now`.
+    fn expired(&self, archive: &[(NaiveDateTime, PathBuf)], now: NaiveDateTime) -> Vec<PathBuf> {
+        let mut buckets = HashSet::new();
+        let mut expired = Vec::new();
+        // Oldest first, so the first snapshot of an hour or day is the one kept
+        for (time, path) in archive {
+            let age = now - *time;
+            let days = |days: u32| Duration::days(days as i64);
+            if age <= days(self.keep_all_days) {
+                continue;
+            }
+            let bucket = if age <= days(self.keep_hourly_days) {
+                (time.date(), Some(time.hour()))
+            } else if self.keep_daily_days.is_none_or(|d| age <= days(d)) {
+                (time.date(), None)
+            } else {
+                expired.push(path.clone());
+                continue;
+            };
+            if !buckets.insert(bucket) {
+                expired.push(path.clone());
+            }
+        }
+        expired
+    }
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            keep_all_days: Self::default_keep_all_days(),
+            keep_hourly_days: Self::default_keep_hourly_days(),
+            keep_daily_days: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveConfig {
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub retention: Retention,
+}
+
+/// All archived snapshots below `dir` with their local time, oldest first.
+pub fn files(dir: &Path) -> io::Result<Vec<(NaiveDateTime, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let relative = path.strip_prefix(dir).unwrap().to_string_lossy();
+            // Everything after the first dot is the extension, e.g. `json.gz`
+            let stem = relative.split('.').next().unwrap().replace('\\', "/");
+            match NaiveDateTime::parse_from_str(&stem, PATH_FORMAT) {
+                Ok(time) => files.push((time, path)),
+                Err(_) => trace!("Ignoring {} in archive", path.display()),
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The decompressed content of an archived snapshot.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    let name = path.to_string_lossy();
+    if name.ends_with(".gz") {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&data[..]).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    } else if name.ends_with(".zst") {
+        zstd::decode_all(&data[..])
+    } else {
+        Ok(data)
+    }
+}
+
+/// Deletes expired snapshots and directories left empty by that.
+fn prune(dir: &Path, retention: &Retention, now: NaiveDateTime) -> io::Result<usize> {
+    let expired = retention.expired(&files(dir)?, now);
+    for path in &expired {
+        fs::remove_file(path)?;
+        for parent in path.ancestors().skip(1).take_while(|p| *p != dir) {
+            // Fails for directories which still have files, which is fine
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(expired.len())
+}
+
+/// Keeps every snapshot as compressed JSON in a dated directory tree.
+pub struct ArchiveSink {
+    config: ArchiveConfig,
+    last_prune: Mutex<Option<DateTime<Local>>>,
+}
+
+impl ArchiveSink {
+    pub fn new(config: ArchiveConfig) -> Self {
+        ArchiveSink {
+            config,
+            last_prune: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for ArchiveSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let time = snapshot.metadata.time;
+        let path = self.config.dir.join(format!(
+            "{}.{}",
+            time.format(PATH_FORMAT),
+            self.config.compression.extension()
+        ));
+        let json = serde_json::to_vec(&snapshot.to_json())?;
+        let data = self.config.compression.compress(&json)?;
+        trace!("Archiving to {}", path.display());
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        tokio::fs::write(&path, data).await?;
+
+        // Thinning out only changes anything once an hour
+        let mut last_prune = self.last_prune.lock().await;
+        if last_prune.is_none_or(|last| time - last >= Duration::hours(1)) {
+            let dir = self.config.dir.clone();
+            let retention = self.config.retention;
+            let removed =
+                tokio::task::spawn_blocking(move || prune(&dir, &retention, time.naive_local()))
+                    .await??;
+            debug!("Removed {} expired snapshot(s) from archive", removed);
+            *last_prune = Some(time);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use super::{files, ArchiveConfig, ArchiveSink, Compression, Retention};
+    use crate::{sinks::Sink, snapshot::Snapshot, testing::temp_dir};
+
+    #[test]
+    fn retention() {
+        let now = NaiveDate::from_ymd_opt(2021, 6, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let archive: Vec<_> = [
+            Duration::days(400),
+            Duration::days(100) + Duration::hours(2),
+            Duration::days(100) + Duration::hours(1),
+            Duration::days(30) + Duration::minutes(40),
+            Duration::days(30) + Duration::minutes(20),
+            Duration::days(30) + Duration::minutes(10),
+            Duration::days(1) + Duration::minutes(2),
+            Duration::days(1) + Duration::minutes(1),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, age)| (now - *age, i.to_string().into()))
+        .collect();
+
+        let mut retention = Retention::default();
+        let expired = |r: &Retention| -> Vec<String> {
+            r.expired(&archive, now)
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect()
+        };
+        // 10:00 and 11:00 are on the same day, 11:20, 11:40 and 11:50 in the same hour
+        assert_eq!(expired(&retention), ["2", "4", "5"]);
+        retention.keep_daily_days = Some(365);
+        assert_eq!(expired(&retention), ["0", "2", "4", "5"]);
+    }
+
+    #[tokio::test]
+    async fn compressed_snapshots() {
+        let temp = temp_dir("archive");
+        let dir = temp.path();
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let sink = ArchiveSink::new(ArchiveConfig {
+                dir: dir.to_path_buf(),
+                compression,
+                retention: Retention::default(),
+            });
+            sink.send(&Snapshot::example()).await.unwrap();
+        }
+
+        let files = files(dir).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|(_, p)| p.strip_prefix(dir).unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["2021/06/01/120000.json.gz", "2021/06/01/120000.json.zst"]
+        );
+        for (time, path) in &files {
+            assert_eq!(time.to_string(), "2021-06-01 12:00:00");
+            assert!(path.metadata().unwrap().len() > 0);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+use super::{Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotation {
+    #[default]
+    Never,
+    /// A file per local day, named `<stem>.<YYYY-MM-DD>.<extension>`.
+    Daily,
+    /// Moves the file aside as `<stem>.<YYYYMMDDHHMMSS>.<extension>` once it reaches `max_bytes`.
+    Size,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvConfig {
+    pub path: PathBuf,
+    /// Information keys to write, all keys with a parsed value if empty.
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default = "CsvConfig::default_devices")]
+    pub devices: bool,
+    #[serde(default)]
+    pub rotate: Rotation,
+    #[serde(default = "CsvConfig::default_max_bytes")]
+    pub max_bytes: u64,
+}
+
+impl CsvConfig {
+    fn default_devices() -> bool {
+        true
+    }
+
+    fn default_max_bytes() -> u64 {
+        10 * 1024 * 1024
+    }
+
+    /// `path` with `suffix` inserted before the extension.
+    fn with_suffix(&self, suffix: &str) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, suffix, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, suffix),
+        };
+        self.path.with_file_name(name)
+    }
+
+    fn current_path(&self, time: DateTime<Local>) -> PathBuf {
+        match self.rotate {
+            Rotation::Daily => self.with_suffix(&time.format("%Y-%m-%d").to_string()),
+            Rotation::Never | Rotation::Size => self.path.clone(),
+        }
+    }
+
+    /// Column names and values of one row, without the time.
+    fn values(&self, snapshot: &Snapshot) -> Vec<(String, String)> {
+        let mut values: Vec<_> = if self.keys.is_empty() {
+            let mut info: Vec<_> = snapshot
+                .info
+                .iter()
+                .filter_map(|(key, i)| Some((key.clone(), i.parsed.as_ref()?.value.to_string())))
+                .collect();
+            info.sort();
+            info
+        } else {
+            self.keys
+                .iter()
+                .map(|key| {
+                    let value = snapshot.info.get(key).map_or_else(String::new, |i| {
+                        i.parsed
+                            .as_ref()
+                            .map_or_else(|| i.value.clone(), |p| p.value.to_string())
+                    });
+                    (key.clone(), value)
+                })
+                .collect()
+        };
+        if self.devices {
+            for (name, _, count) in snapshot.devices.counts() {
+                values.push((name.to_string(), count.to_string()));
+            }
+        }
+        values
+    }
+}
+
+fn record(fields: &[String]) -> Result<Vec<u8>, SinkError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields)?;
+    Ok(writer.into_inner().map_err(|e| e.to_string())?)
+}
+
+/// Columns of an existing file, from its header line.
+fn read_header(path: &Path) -> Option<Vec<String>> {
+    let mut line = String::new();
+    BufReader::new(std::fs::File::open(path).ok()?)
+        .read_line(&mut line)
+        .ok()?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(line.as_bytes());
+    let header = reader.records().next()?.ok()?;
+    Some(header.iter().map(str::to_owned).collect())
+}
+
+/// The file currently appended to and its columns.
+struct Current {
+    path: PathBuf,
+    columns: Vec<String>,
+}
+
+/// Appends a row per snapshot to a CSV file for spreadsheets.
+pub struct CsvSink {
+    config: CsvConfig,
+    current: Mutex<Option<Current>>,
+}
+
+impl CsvSink {
+    pub fn new(config: CsvConfig) -> Self {
+        CsvSink {
+            config,
+            current: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for CsvSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let time = snapshot.metadata.time;
+        let path = self.config.current_path(time);
+        let mut current = self.current.lock().await;
+        if current.as_ref().map(|c| &c.path) != Some(&path) {
+            *current = Some(Current {
+                columns: read_header(&path).unwrap_or_default(),
+                path: path.clone(),
+            });
+        }
+        let current = current.as_mut().unwrap();
+
+        let size = fs::metadata(&path).await.ok().map(|m| m.len());
+        if size.is_none() {
+            current.columns.clear();
+        }
+
+        let values = self.config.values(snapshot);
+        let new_columns: Vec<_> = values
+            .iter()
+            .map(|(column, _)| column)
+            .filter(|column| !current.columns.contains(column))
+            .cloned()
+            .collect();
+        let too_large = self.config.rotate == Rotation::Size
+            && size.is_some_and(|s| s >= self.config.max_bytes);
+        let mut header = current.columns.is_empty();
+        // Columns are only ever added, so the next file keeps the order of the previous one
+        if !header && (too_large || !new_columns.is_empty()) {
+            let rotated = self
+                .config
+                .with_suffix(&time.format("%Y%m%d%H%M%S").to_string());
+            info!(
+                "Moving {} to {} ({})",
+                path.display(),
+                rotated.display(),
+                if too_large { "size" } else { "new columns" }
+            );
+            fs::rename(&path, &rotated).await?;
+            header = true;
+        }
+        if current.columns.is_empty() {
+            current.columns.push("time".to_string());
+        }
+        current.columns.extend(new_columns);
+
+        let mut data = if header {
+            record(&current.columns)?
+        } else {
+            Vec::new()
+        };
+        let row: Vec<_> = current
+            .columns
+            .iter()
+            .map(|column| match column.as_str() {
+                "time" => time.to_rfc3339(),
+                _ => values
+                    .iter()
+                    .find(|(c, _)| c == column)
+                    .map_or_else(String::new, |(_, v)| v.clone()),
+            })
+            .collect();
+        data.extend(record(&row)?);
+
+        trace!("Appending to {}", path.display());
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(&data).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{CsvConfig, CsvSink, Rotation};
+    use crate::{sinks::Sink, snapshot::Snapshot, testing::temp_dir};
+
+    #[tokio::test]
+    async fn rows_and_new_columns() {
+        let temp = temp_dir("csv");
+        let dir = temp.path();
+        let sink = CsvSink::new(CsvConfig {
+            path: dir.join("metrics.csv"),
+            keys: Vec::new(),
+            devices: false,
+            rotate: Rotation::Daily,
+            max_bytes: 0,
+        });
+
+        let mut snapshot = Snapshot::example();
+        sink.send(&snapshot).await.unwrap();
+        sink.send(&snapshot).await.unwrap();
+        let day = dir.join("metrics.2021-06-01.csv");
+        let time = snapshot.metadata.time.to_rfc3339();
+        assert_eq!(
+            fs::read_to_string(&day).unwrap(),
+            format!(
+                "time,rsrp,sinr,totaldownload\n{0},-109,-1,2048\n{0},-109,-1,2048\n",
+                time
+            )
+        );
+
+        // A firmware update adds a key, the file is moved aside and the new one has the column
+        let mut rsrq = snapshot.info["sinr"].clone();
+        rsrq.parsed.as_mut().unwrap().value = -11.0;
+        snapshot.info.insert("rsrq".to_string(), rsrq);
+        sink.send(&snapshot).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&day).unwrap(),
+            format!(
+                "time,rsrp,sinr,totaldownload,rsrq\n{},-109,-1,2048,-11\n",
+                time
+            )
+        );
+        assert!(dir.join("metrics.20210601120000.csv").exists());
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{Format, Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize)]
+pub struct FileConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// Hidden file next to `path` which is renamed over it, so readers never see a partial file.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Replaces the file at `path` with `contents` in one step.
+pub async fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let temp = temp_path(path);
+    tokio::fs::write(&temp, contents).await?;
+    tokio::fs::rename(&temp, path).await
+}
+
+pub struct FileSink {
+    config: FileConfig,
+}
+
+impl FileSink {
+    pub fn new(config: FileConfig) -> Self {
+        FileSink { config }
+    }
+}
+
+#[async_trait]
+impl Sink for FileSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        trace!("Outputting to {}", self.config.path.display());
+        write_atomic(&self.config.path, self.config.format.render(snapshot)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{FileConfig, FileSink};
+    use crate::{
+        sinks::{Format, Sink},
+        snapshot::Snapshot,
+        testing::temp_dir,
+    };
+
+    #[tokio::test]
+    async fn replaces_file() {
+        let temp = temp_dir("file");
+        let dir = temp.path();
+        let path = dir.join("huawei.prom");
+        fs::write(&path, "old").unwrap();
+        let sink = FileSink::new(FileConfig {
+            path: path.clone(),
+            format: Format::Prometheus,
+        });
+        sink.send(&Snapshot::example()).await.unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("huawei_metrics_last_success_timestamp_seconds 1622"));
+        // Only the target is left behind
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+use super::{mqtt::object_id, Sink, SinkError};
+use crate::{snapshot::Snapshot, SERIAL_NUMBER};
+
+/// A numeric value of the snapshot with the parts metric path templates are built from.
+pub struct Point {
+    pub group: &'static str,
+    pub key: String,
+    pub value: f64,
+}
+
+/// Parsed information values grouped by unit, followed by the device counts.
+pub fn points(snapshot: &Snapshot) -> Vec<Point> {
+    let mut info: Vec<_> = snapshot.info.iter().collect();
+    info.sort_by_key(|(key, _)| key.as_str());
+    let mut points: Vec<_> = info
+        .into_iter()
+        .filter_map(|(key, information)| {
+            let parsed = information.parsed.as_ref()?;
+            let group = match parsed.unit.as_str() {
+                "dBm" | "dB" => "signal",
+                "Mbps" | "GB" | "MB" | "KB" | "B" => "traffic",
+                _ => "info",
+            };
+            Some(Point {
+                group,
+                key: object_id(key),
+                value: parsed.value,
+            })
+        })
+        .collect();
+    for (name, _, count) in snapshot.devices.counts() {
+        points.push(Point {
+            group: "devices",
+            key: name.to_string(),
+            value: count as f64,
+        });
+    }
+    points
+}
+
+/// Graphite-safe identifier of the router, dots would add path levels.
+pub fn router(snapshot: &Snapshot) -> String {
+    object_id(&snapshot.metadata.router_host)
+}
+
+pub fn serial(snapshot: &Snapshot) -> String {
+    snapshot
+        .info
+        .get(SERIAL_NUMBER)
+        .map_or_else(String::new, |i| object_id(&i.value))
+}
+
+/// Replaces `{router}`, `{serial}`, `{group}` and `{key}` in `template`.
+pub fn render_path(template: &str, snapshot: &Snapshot, point: &Point) -> String {
+    template
+        .replace("{router}", &router(snapshot))
+        .replace("{serial}", &serial(snapshot))
+        .replace("{group}", point.group)
+        .replace("{key}", &point.key)
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphiteProtocol {
+    Plaintext,
+    Pickle,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphiteConfig {
+    pub host: String,
+    /// Defaults to 2003 for plaintext and 2004 for pickle.
+    pub port: Option<u16>,
+    #[serde(default = "GraphiteConfig::default_protocol")]
+    pub protocol: GraphiteProtocol,
+    #[serde(default = "GraphiteConfig::default_template")]
+    pub template: String,
+}
+
+impl GraphiteConfig {
+    fn default_protocol() -> GraphiteProtocol {
+        GraphiteProtocol::Plaintext
+    }
+
+    fn default_template() -> String {
+        "huawei.{router}.{group}.{key}".to_string()
+    }
+
+    fn metrics(&self, snapshot: &Snapshot) -> Vec<(String, f64)> {
+        points(snapshot)
+            .iter()
+            .map(|p| (render_path(&self.template, snapshot, p), p.value))
+            .collect()
+    }
+}
+
+fn plaintext(metrics: &[(String, f64)], timestamp: i64) -> Vec<u8> {
+    metrics
+        .iter()
+        .map(|(path, value)| format!("{} {} {}\n", path, value, timestamp))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// A length-prefixed protocol 2 pickle of `[(path, (timestamp, value)), ...]`.
+fn pickle(metrics: &[(String, f64)], timestamp: i64) -> Vec<u8> {
+    let mut data = vec![0x80, 2, b']', b'('];
+    for (path, value) in metrics {
+        data.push(b'X');
+        data.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        data.extend_from_slice(path.as_bytes());
+        data.push(b'J');
+        data.extend_from_slice(&(timestamp as i32).to_le_bytes());
+        data.push(b'G');
+        data.extend_from_slice(&value.to_be_bytes());
+        // TUPLE2 twice: (timestamp, value), then (path, (timestamp, value))
+        data.extend_from_slice(&[0x86, 0x86]);
+    }
+    data.extend_from_slice(b"e.");
+
+    let mut message = (data.len() as u32).to_be_bytes().to_vec();
+    message.extend_from_slice(&data);
+    message
+}
+
+/// Sends the information values and device counts to Graphite's carbon receiver.
+pub struct GraphiteSink {
+    config: GraphiteConfig,
+}
+
+impl GraphiteSink {
+    pub fn new(config: GraphiteConfig) -> Self {
+        GraphiteSink { config }
+    }
+}
+
+#[async_trait]
+impl Sink for GraphiteSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let metrics = self.config.metrics(snapshot);
+        let timestamp = snapshot.metadata.time.timestamp();
+        let (port, data) = match self.config.protocol {
+            GraphiteProtocol::Plaintext => (2003, plaintext(&metrics, timestamp)),
+            GraphiteProtocol::Pickle => (2004, pickle(&metrics, timestamp)),
+        };
+        let port = self.config.port.unwrap_or(port);
+
+        let mut stream = TcpStream::connect((self.config.host.as_str(), port)).await?;
+        stream.write_all(&data).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pickle, plaintext, GraphiteConfig};
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn paths() {
+        let config: GraphiteConfig = toml::from_str(r#"host = "localhost""#).unwrap();
+        let metrics = config.metrics(&Snapshot::example());
+        assert_eq!(
+            String::from_utf8(plaintext(&metrics[..2], 1)).unwrap(),
+            "huawei.192_168_8_1.signal.rsrp -109 1\nhuawei.192_168_8_1.signal.sinr -1 1\n"
+        );
+        assert!(metrics.contains(&("huawei.192_168_8_1.devices.online_devices".to_string(), 1.0)));
+
+        let data = pickle(&[("a.b".to_string(), 1.5)], 2);
+        let mut expected = vec![0, 0, 0, 30, 0x80, 2, b']', b'(', b'X', 3, 0, 0, 0];
+        expected.extend_from_slice(b"a.bJ\x02\x00\x00\x00G");
+        expected.extend_from_slice(&1.5f64.to_be_bytes());
+        expected.extend_from_slice(b"\x86\x86e.");
+        assert_eq!(data, expected);
+    }
+}
@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::{
+    http::ClientConfig,
+    mqtt::{hass_unit, object_id},
+    Sink, SinkError,
+};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize)]
+pub struct HomeAssistantConfig {
+    /// Base URL of Home Assistant, e.g. `http://homeassistant.local:8123`.
+    pub url: String,
+    /// POSTs the snapshot to `/api/webhook/<webhook_id>`.
+    pub webhook_id: Option<String>,
+    /// Sends a flat `{"sinr": -1, ...}` object to the webhook instead of the full JSON output.
+    #[serde(default)]
+    pub flatten: bool,
+    /// Long-lived access token, sets `sensor.<entity_prefix>_<key>` states through the REST API.
+    pub token: Option<String>,
+    #[serde(default = "HomeAssistantConfig::default_entity_prefix")]
+    pub entity_prefix: String,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl HomeAssistantConfig {
+    fn default_entity_prefix() -> String {
+        "huawei".to_string()
+    }
+}
+
+/// One entry per information value and device count.
+struct Entity {
+    key: String,
+    name: String,
+    state: Value,
+    unit: Option<String>,
+}
+
+fn entities(snapshot: &Snapshot) -> Vec<Entity> {
+    let mut entities: Vec<_> = snapshot
+        .info
+        .iter()
+        .map(|(key, information)| {
+            let (state, unit) = match &information.parsed {
+                Some(parsed) => (json!(parsed.value), Some(parsed.unit.clone())),
+                None => (json!(information.value), None),
+            };
+            Entity {
+                key: key.clone(),
+                name: information.label.clone(),
+                state,
+                unit,
+            }
+        })
+        .collect();
+    entities.sort_by(|a, b| a.key.cmp(&b.key));
+    for (name, help, count) in snapshot.devices.counts() {
+        entities.push(Entity {
+            key: name.to_string(),
+            name: help.to_string(),
+            state: json!(count),
+            unit: None,
+        });
+    }
+    entities
+}
+
+/// `{"<key>": <parsed value or raw value>}` for all entities.
+fn flattened(snapshot: &Snapshot) -> Value {
+    let mut map = Map::new();
+    for entity in entities(snapshot) {
+        map.insert(entity.key, entity.state);
+    }
+    Value::Object(map)
+}
+
+/// Sends the snapshot to Home Assistant via a webhook trigger and/or the states REST API.
+pub struct HomeAssistantSink {
+    client: Client,
+    config: HomeAssistantConfig,
+}
+
+impl HomeAssistantSink {
+    pub fn new(config: HomeAssistantConfig) -> Self {
+        if config.webhook_id.is_none() && config.token.is_none() {
+            warn!("Home Assistant sink has neither webhook_id nor token, it won't send anything");
+        }
+        HomeAssistantSink {
+            client: config.client.client(),
+            config,
+        }
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/api/{}", self.config.url.trim_end_matches('/'), path)
+    }
+}
+
+#[async_trait]
+impl Sink for HomeAssistantSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        if let Some(webhook_id) = &self.config.webhook_id {
+            let payload = if self.config.flatten {
+                flattened(snapshot)
+            } else {
+                Value::Object(snapshot.to_json())
+            };
+            let request = self
+                .client
+                .post(self.endpoint(&format!("webhook/{}", webhook_id)))
+                .json(&payload);
+            self.config
+                .client
+                .authorize(request)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        if let Some(token) = &self.config.token {
+            for entity in entities(snapshot) {
+                let mut attributes = json!({ "friendly_name": entity.name });
+                if let Some(unit) = &entity.unit {
+                    let (device_class, unit, state_class) = hass_unit(unit);
+                    attributes["unit_of_measurement"] = json!(unit);
+                    attributes["state_class"] = json!(state_class);
+                    if let Some(device_class) = device_class {
+                        attributes["device_class"] = json!(device_class);
+                    }
+                }
+                let entity_id = format!(
+                    "sensor.{}_{}",
+                    self.config.entity_prefix,
+                    object_id(&entity.key)
+                );
+                trace!("Setting state of {}", entity_id);
+                self.client
+                    .post(self.endpoint(&format!("states/{}", entity_id)))
+                    .bearer_auth(token)
+                    .json(&json!({ "state": entity.state, "attributes": attributes }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::flattened;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn flattened_payload() {
+        let payload = flattened(&Snapshot::example());
+        assert_eq!(payload["sinr"], json!(-1.0));
+        assert_eq!(payload["serialNumber"], json!("ABC123"));
+        assert_eq!(payload["total_devices"], json!(2));
+    }
+}
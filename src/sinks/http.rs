@@ -0,0 +1,124 @@
+use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::{Certificate, Client, ClientBuilder, Identity, Method, RequestBuilder};
+use serde::Deserialize;
+
+use super::{Format, Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+/// Connection settings shared by all sinks talking HTTP.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ClientConfig {
+    pub basic_auth: Option<BasicAuth>,
+    /// PEM file with an additional CA certificate to trust.
+    pub ca_cert: Option<PathBuf>,
+    /// PKCS#12 archive with a client certificate and key.
+    pub client_pkcs12: Option<PathBuf>,
+    #[serde(default)]
+    pub client_pkcs12_password: String,
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    pub timeout_secs: Option<u64>,
+}
+
+impl ClientConfig {
+    pub fn client(&self) -> Client {
+        self.builder().build().unwrap()
+    }
+
+    pub fn builder(&self) -> ClientBuilder {
+        let mut builder = ClientBuilder::new()
+            .timeout(Duration::from_secs(self.timeout_secs.unwrap_or(30)))
+            .danger_accept_invalid_certs(self.insecure_skip_verify);
+        if let Some(path) = &self.ca_cert {
+            let pem = fs::read(path)
+                .unwrap_or_else(|e| panic!("Unable to read {}: {:#}", path.display(), e));
+            builder = builder
+                .add_root_certificate(Certificate::from_pem(&pem).expect("Invalid CA certificate"));
+        }
+        if let Some(path) = &self.client_pkcs12 {
+            let der = fs::read(path)
+                .unwrap_or_else(|e| panic!("Unable to read {}: {:#}", path.display(), e));
+            builder = builder.identity(
+                Identity::from_pkcs12_der(&der, &self.client_pkcs12_password)
+                    .expect("Invalid client certificate"),
+            );
+        }
+        builder
+    }
+
+    pub fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.basic_auth {
+            Some(auth) => request.basic_auth(&auth.username, auth.password.as_ref()),
+            None => request,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HttpConfig {
+    pub url: String,
+    #[serde(default = "HttpConfig::default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl HttpConfig {
+    fn default_method() -> String {
+        "POST".to_string()
+    }
+}
+
+/// Pushes the rendered snapshot as request body to an HTTP endpoint.
+pub struct HttpSink {
+    client: Client,
+    method: Method,
+    config: HttpConfig,
+}
+
+impl HttpSink {
+    pub fn new(config: HttpConfig) -> Self {
+        HttpSink {
+            client: config.client.client(),
+            method: config
+                .method
+                .to_ascii_uppercase()
+                .parse()
+                .expect("Invalid HTTP method for http sink"),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for HttpSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let mut request = self
+            .client
+            .request(self.method.clone(), &self.config.url)
+            .header("Content-Type", self.config.format.content_type())
+            .body(self.config.format.render(snapshot));
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        self.config
+            .client
+            .authorize(request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::{http::ClientConfig, Sink, SinkError};
+use crate::{influx, snapshot::Snapshot};
+
+#[derive(Debug, Deserialize)]
+pub struct InfluxDbConfig {
+    /// Base URL of the InfluxDB server, e.g. `http://localhost:8086`.
+    pub url: String,
+    /// InfluxDB 2 organization, writes to `/api/v2/write` when `bucket` is set.
+    pub org: Option<String>,
+    pub bucket: Option<String>,
+    /// API token, sent as `Authorization: Token <token>`.
+    pub token: Option<String>,
+    /// InfluxDB 1 database, writes to `/write`.
+    pub database: Option<String>,
+    pub retention_policy: Option<String>,
+    /// Maximum number of lines per write request.
+    #[serde(default = "InfluxDbConfig::default_batch_size")]
+    pub batch_size: usize,
+    /// Lines kept for later writes while the server is unreachable, the oldest are dropped first.
+    #[serde(default = "InfluxDbConfig::default_max_pending_lines")]
+    pub max_pending_lines: usize,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl InfluxDbConfig {
+    fn default_batch_size() -> usize {
+        5000
+    }
+
+    fn default_max_pending_lines() -> usize {
+        100_000
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    lines: VecDeque<String>,
+    /// Time of the last queued snapshot, a retried snapshot isn't queued twice.
+    queued: Option<DateTime<Local>>,
+}
+
+impl Pending {
+    fn queue(&mut self, snapshot: &Snapshot, max: usize) {
+        if self.queued == Some(snapshot.metadata.time) {
+            return;
+        }
+        self.queued = Some(snapshot.metadata.time);
+        self.lines.extend(influx::lines(snapshot));
+        if self.lines.len() > max {
+            let dropped = self.lines.len() - max;
+            warn!("Dropping {} pending InfluxDB lines", dropped);
+            self.lines.drain(..dropped);
+        }
+    }
+}
+
+/// Writes line protocol to the InfluxDB 1 or 2 HTTP write API, unwritten lines are sent with the next snapshot.
+pub struct InfluxDbSink {
+    client: Client,
+    write_url: String,
+    query: Vec<(&'static str, String)>,
+    config: InfluxDbConfig,
+    pending: Mutex<Pending>,
+}
+
+impl InfluxDbSink {
+    pub fn new(config: InfluxDbConfig) -> Self {
+        let base = config.url.trim_end_matches('/');
+        let mut query = vec![("precision", "ns".to_string())];
+        let write_url = match (&config.bucket, &config.database) {
+            (Some(bucket), _) => {
+                query.push(("bucket", bucket.clone()));
+                if let Some(org) = &config.org {
+                    query.push(("org", org.clone()));
+                }
+                format!("{}/api/v2/write", base)
+            }
+            (None, Some(database)) => {
+                query.push(("db", database.clone()));
+                if let Some(rp) = &config.retention_policy {
+                    query.push(("rp", rp.clone()));
+                }
+                format!("{}/write", base)
+            }
+            (None, None) => panic!("InfluxDB sink needs either a bucket or a database"),
+        };
+        InfluxDbSink {
+            client: config.client.client(),
+            write_url,
+            query,
+            pending: Mutex::new(Pending::default()),
+            config,
+        }
+    }
+
+    fn request(&self, body: String) -> RequestBuilder {
+        let mut request = self
+            .client
+            .post(&self.write_url)
+            .query(&self.query)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body);
+        if let Some(token) = &self.config.token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        self.config.client.authorize(request)
+    }
+}
+
+#[async_trait]
+impl Sink for InfluxDbSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let mut pending = self.pending.lock().await;
+        pending.queue(snapshot, self.config.max_pending_lines);
+
+        while !pending.lines.is_empty() {
+            let batch = pending.lines.len().min(self.config.batch_size.max(1));
+            let body = pending
+                .lines
+                .range(..batch)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n");
+            trace!("Writing {} lines to {}", batch, self.write_url);
+            self.request(body).send().await?.error_for_status()?;
+            pending.lines.drain(..batch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pending;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn pending_lines() {
+        let mut snapshot = Snapshot::example();
+        let mut pending = Pending::default();
+        pending.queue(&snapshot, 100);
+        let per_snapshot = pending.lines.len();
+        pending.queue(&snapshot, 100);
+        assert_eq!(pending.lines.len(), per_snapshot);
+
+        snapshot.metadata.time += chrono::Duration::minutes(1);
+        pending.queue(&snapshot, per_snapshot + 1);
+        assert_eq!(pending.lines.len(), per_snapshot + 1);
+        assert!(pending.lines.back().unwrap().ends_with(&format!(
+            " {}",
+            snapshot.metadata.time.timestamp_nanos_opt().unwrap()
+        )));
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{http::ClientConfig, Sink, SinkError};
+use crate::{events::Event, snapshot::Snapshot};
+
+#[derive(Debug, Deserialize)]
+pub struct LokiConfig {
+    /// Base URL of Loki, e.g. `http://localhost:3100`.
+    pub url: String,
+    #[serde(default = "LokiConfig::default_job")]
+    pub job: String,
+    /// Additional stream labels, `router` and `event` are always set.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Sent as `X-Scope-OrgID` to multi-tenant Loki.
+    pub tenant_id: Option<String>,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl LokiConfig {
+    fn default_job() -> String {
+        "huawei_metrics".to_string()
+    }
+
+    /// Body for the push API, a stream per router and event type with JSON log lines.
+    fn push_body(&self, events: &[Event]) -> Value {
+        let mut streams: BTreeMap<(&str, &str), Vec<Value>> = BTreeMap::new();
+        for event in events {
+            let line = Value::Object(event.to_json()).to_string();
+            let timestamp = event.time.timestamp_nanos_opt().unwrap().to_string();
+            streams
+                .entry((&event.router, event.kind.name()))
+                .or_default()
+                .push(json!([timestamp, line]));
+        }
+        let streams: Vec<_> = streams
+            .into_iter()
+            .map(|((router, event), values)| {
+                let mut labels = self.labels.clone();
+                labels.insert("job".to_string(), self.job.clone());
+                labels.insert("router".to_string(), router.to_string());
+                labels.insert("event".to_string(), event.to_string());
+                json!({ "stream": labels, "values": values })
+            })
+            .collect();
+        json!({ "streams": streams })
+    }
+}
+
+/// Ships events to the Loki push API, snapshots are left to the other sinks.
+pub struct LokiSink {
+    client: Client,
+    push_url: String,
+    config: LokiConfig,
+}
+
+impl LokiSink {
+    pub fn new(config: LokiConfig) -> Self {
+        LokiSink {
+            client: config.client.client(),
+            push_url: format!("{}/loki/api/v1/push", config.url.trim_end_matches('/')),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for LokiSink {
+    async fn send(&self, _snapshot: &Snapshot) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    async fn events(&self, events: &[Event]) -> Result<(), SinkError> {
+        let mut request = self
+            .client
+            .post(&self.push_url)
+            .json(&self.config.push_body(events));
+        if let Some(tenant) = &self.config.tenant_id {
+            request = request.header("X-Scope-OrgID", tenant);
+        }
+        trace!("Pushing {} event(s) to {}", events.len(), self.push_url);
+        self.config
+            .client
+            .authorize(request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use serde_json::json;
+
+    use super::LokiConfig;
+    use crate::events::Event;
+
+    #[test]
+    fn streams_per_event_type() {
+        let config: LokiConfig = toml::from_str(
+            r#"
+            url = "http://localhost:3100"
+            labels = { site = "cabin" }
+            "#,
+        )
+        .unwrap();
+        let time = Local.with_ymd_and_hms(2021, 6, 1, 12, 0, 0).unwrap();
+        let event = Event::scrape_failed(time, "192.168.8.1", "login timed out");
+        let body = config.push_body(&[event.clone(), event]);
+        let line = r#"{"event":"scrape_failed","message":"Scraping the router failed: login timed out","reason":"login timed out","router":"192.168.8.1"}"#;
+        let timestamp = time.timestamp_nanos_opt().unwrap().to_string();
+        assert_eq!(
+            body,
+            json!({ "streams": [{
+                "stream": {
+                    "event": "scrape_failed",
+                    "job": "huawei_metrics",
+                    "router": "192.168.8.1",
+                    "site": "cabin"
+                },
+                "values": [[timestamp, line], [timestamp, line]]
+            }]})
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::{error::Error, time::Duration};
+
+use async_trait::async_trait;
+use futures::future::{join_all, BoxFuture};
+use serde::Deserialize;
+use tokio::time::sleep;
+
+use crate::{events::Event, influx, metrics, snapshot::Snapshot};
+
+pub mod agentx;
+pub mod archive;
+pub mod csv;
+pub mod file;
+pub mod graphite;
+pub mod home_assistant;
+pub mod http;
+pub mod influxdb;
+pub mod loki;
+pub mod mqtt;
+pub mod otlp;
+pub mod pushgateway;
+pub mod remote_write;
+pub mod statsd;
+pub mod stdout;
+pub mod syslog;
+pub mod template;
+pub mod zabbix;
+
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+/// A destination for the gathered data.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError>;
+
+    /// Called with the events since the previous scrape, only event sinks ship them.
+    async fn events(&self, _events: &[Event]) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    /// Called once when the exporter is stopped in `--interval` mode.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Json,
+    Prometheus,
+    Influx,
+}
+
+impl Format {
+    pub fn render(&self, snapshot: &Snapshot) -> String {
+        match self {
+            Format::Json => serde_json::to_string_pretty(&snapshot.to_json()).unwrap(),
+            Format::Prometheus => metrics::encode_text(&metrics::registry(snapshot)),
+            Format::Influx => influx::encode(snapshot),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Prometheus => "text/plain; version=0.0.4",
+            Format::Influx => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// How often a failing sink is tried again, the backoff doubles after every attempt.
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct RetryPolicy {
+    #[serde(default = "RetryPolicy::default_attempts")]
+    pub attempts: u32,
+    #[serde(default = "RetryPolicy::default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    fn default_attempts() -> u32 {
+        1
+    }
+
+    fn default_backoff_ms() -> u64 {
+        1000
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: Self::default_attempts(),
+            backoff_ms: Self::default_backoff_ms(),
+        }
+    }
+}
+
+/// One `[[sinks]]` entry of the config file.
+#[derive(Debug, Deserialize)]
+pub struct SinkEntry {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(flatten)]
+    pub config: SinkConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Stdout(stdout::StdoutConfig),
+    File(file::FileConfig),
+    Http(http::HttpConfig),
+    Pushgateway(pushgateway::PushgatewayConfig),
+    Mqtt(mqtt::MqttConfig),
+    HomeAssistant(home_assistant::HomeAssistantConfig),
+    Influxdb(influxdb::InfluxDbConfig),
+    RemoteWrite(remote_write::RemoteWriteConfig),
+    Otlp(otlp::OtlpConfig),
+    Graphite(graphite::GraphiteConfig),
+    Statsd(statsd::StatsdConfig),
+    Zabbix(zabbix::ZabbixConfig),
+    Agentx(agentx::AgentxConfig),
+    Csv(csv::CsvConfig),
+    Archive(archive::ArchiveConfig),
+    Loki(loki::LokiConfig),
+    Syslog(syslog::SyslogConfig),
+    Template(template::TemplateConfig),
+}
+
+impl SinkConfig {
+    fn describe(&self) -> String {
+        match self {
+            SinkConfig::Stdout(_) => "stdout".to_string(),
+            SinkConfig::File(c) => format!("file:{}", c.path.display()),
+            SinkConfig::Http(c) => format!("http:{}", c.url),
+            SinkConfig::Pushgateway(c) => format!("pushgateway:{}", c.url),
+            SinkConfig::Mqtt(c) => format!("mqtt:{}:{}", c.host, c.port),
+            SinkConfig::HomeAssistant(c) => format!("home_assistant:{}", c.url),
+            SinkConfig::Influxdb(c) => format!("influxdb:{}", c.url),
+            SinkConfig::RemoteWrite(c) => format!("remote_write:{}", c.url),
+            SinkConfig::Otlp(c) => format!("otlp:{}", c.url),
+            SinkConfig::Graphite(c) => format!("graphite:{}", c.host),
+            SinkConfig::Statsd(c) => format!("statsd:{}:{}", c.host, c.port),
+            SinkConfig::Zabbix(c) => format!("zabbix:{}:{}", c.server, c.port),
+            SinkConfig::Agentx(c) => format!("agentx:{}", c.master),
+            SinkConfig::Csv(c) => format!("csv:{}", c.path.display()),
+            SinkConfig::Archive(c) => format!("archive:{}", c.dir.display()),
+            SinkConfig::Loki(c) => format!("loki:{}", c.url),
+            SinkConfig::Syslog(c) => format!("syslog:{}:{}", c.host, c.port),
+            SinkConfig::Template(c) => format!("template:{}", c.template.display()),
+        }
+    }
+}
+
+pub struct ConfiguredSink {
+    name: String,
+    retry: RetryPolicy,
+    sink: Box<dyn Sink>,
+}
+
+impl ConfiguredSink {
+    pub fn new(entry: SinkEntry) -> Self {
+        let config = entry.config;
+        let name = entry.name.unwrap_or_else(|| config.describe());
+        let sink: Box<dyn Sink> = match config {
+            SinkConfig::Stdout(c) => Box::new(stdout::StdoutSink::new(c)),
+            SinkConfig::File(c) => Box::new(file::FileSink::new(c)),
+            SinkConfig::Http(c) => Box::new(http::HttpSink::new(c)),
+            SinkConfig::Pushgateway(c) => Box::new(pushgateway::PushgatewaySink::new(c)),
+            SinkConfig::Mqtt(c) => Box::new(mqtt::MqttSink::new(c)),
+            SinkConfig::HomeAssistant(c) => Box::new(home_assistant::HomeAssistantSink::new(c)),
+            SinkConfig::Influxdb(c) => Box::new(influxdb::InfluxDbSink::new(c)),
+            SinkConfig::RemoteWrite(c) => Box::new(remote_write::RemoteWriteSink::new(c)),
+            SinkConfig::Otlp(c) => Box::new(otlp::OtlpSink::new(c)),
+            SinkConfig::Graphite(c) => Box::new(graphite::GraphiteSink::new(c)),
+            SinkConfig::Statsd(c) => Box::new(statsd::StatsdSink::new(c)),
+            SinkConfig::Zabbix(c) => Box::new(zabbix::ZabbixSink::new(c)),
+            SinkConfig::Agentx(c) => Box::new(agentx::AgentxSink::new(c)),
+            SinkConfig::Csv(c) => Box::new(csv::CsvSink::new(c)),
+            SinkConfig::Archive(c) => Box::new(archive::ArchiveSink::new(c)),
+            SinkConfig::Loki(c) => Box::new(loki::LokiSink::new(c)),
+            SinkConfig::Syslog(c) => Box::new(syslog::SyslogSink::new(c)),
+            SinkConfig::Template(c) => Box::new(template::TemplateSink::new(c)),
+        };
+        ConfiguredSink {
+            name,
+            retry: entry.retry,
+            sink,
+        }
+    }
+
+    /// Runs `attempt` following the retry policy, returns whether it eventually succeeded.
+    async fn retry<'a>(
+        &'a self,
+        attempt: impl Fn() -> BoxFuture<'a, Result<(), SinkError>>,
+    ) -> bool {
+        let mut backoff = Duration::from_millis(self.retry.backoff_ms);
+        let attempts = self.retry.attempts.max(1);
+        for n in 1..=attempts {
+            trace!("Sending to sink {} (attempt {})", self.name, n);
+            match attempt().await {
+                Ok(()) => return true,
+                Err(e) => {
+                    warn!(
+                        "Sink {} failed (attempt {}/{}): {:#}",
+                        self.name, n, attempts, e
+                    );
+                    if n < attempts {
+                        sleep(backoff).await;
+                        backoff *= 2;
+                    }
+                }
+            }
+        }
+        error!("Giving up on sink {}", self.name);
+        false
+    }
+
+    async fn send(&self, snapshot: &Snapshot) -> bool {
+        self.retry(|| self.sink.send(snapshot)).await
+    }
+
+    async fn events(&self, events: &[Event]) -> bool {
+        self.retry(|| self.sink.events(events)).await
+    }
+}
+
+/// Gives all sinks the chance to clean up before the exporter stops scraping.
+pub async fn shutdown(sinks: &[ConfiguredSink]) {
+    let results = join_all(
+        sinks
+            .iter()
+            .map(|sink| async move { (&sink.name, sink.sink.shutdown().await) }),
+    )
+    .await;
+    for (name, result) in results {
+        if let Err(e) = result {
+            warn!("Sink {} failed to shut down: {:#}", name, e);
+        }
+    }
+}
+
+/// Sends `snapshot` to all sinks concurrently, returns the number of sinks that failed.
+pub async fn dispatch(sinks: &[ConfiguredSink], snapshot: &Snapshot) -> usize {
+    join_all(sinks.iter().map(|sink| sink.send(snapshot)))
+        .await
+        .into_iter()
+        .filter(|ok| !ok)
+        .count()
+}
+
+/// Ships `events` to all sinks concurrently, returns the number of sinks that failed.
+pub async fn dispatch_events(sinks: &[ConfiguredSink], events: &[Event]) -> usize {
+    if events.is_empty() {
+        return 0;
+    }
+    join_all(sinks.iter().map(|sink| sink.events(events)))
+        .await
+        .into_iter()
+        .filter(|ok| !ok)
+        .count()
+}
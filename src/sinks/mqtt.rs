@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, QoS};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{Sink, SinkError};
+use crate::{snapshot::Snapshot, Information, DEVICE_NAME, SERIAL_NUMBER, SOFTWARE_VERSION};
+
+#[derive(Debug, Deserialize)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "MqttConfig::default_port")]
+    pub port: u16,
+    #[serde(default = "MqttConfig::default_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// State topics are published below `<base_topic>/<node id>`.
+    #[serde(default = "MqttConfig::default_base_topic")]
+    pub base_topic: String,
+    /// Publishes Home Assistant discovery configs below this prefix, unless set to `""`.
+    #[serde(default = "MqttConfig::default_discovery_prefix")]
+    pub discovery_prefix: String,
+    /// Publishes a `device_tracker` per device known to the router.
+    #[serde(default = "MqttConfig::default_device_trackers")]
+    pub device_trackers: bool,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default)]
+    pub qos: u8,
+}
+
+impl MqttConfig {
+    fn default_port() -> u16 {
+        1883
+    }
+
+    fn default_client_id() -> String {
+        "huawei-metrics".to_string()
+    }
+
+    fn default_base_topic() -> String {
+        "huawei_metrics".to_string()
+    }
+
+    fn default_discovery_prefix() -> String {
+        "homeassistant".to_string()
+    }
+
+    fn default_device_trackers() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Message {
+    topic: String,
+    payload: String,
+    retain: bool,
+}
+
+/// Lowercase identifier with everything but letters and digits replaced by `_`.
+pub fn object_id(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Home Assistant device class, unit of measurement and state class for a parsed unit.
+pub fn hass_unit(unit: &str) -> (Option<&'static str>, &str, &'static str) {
+    match unit {
+        "dBm" | "dB" => (Some("signal_strength"), unit, "measurement"),
+        "Mbps" => (Some("data_rate"), "Mbit/s", "measurement"),
+        "KB" => (Some("data_size"), "kB", "total_increasing"),
+        "GB" | "MB" | "B" => (Some("data_size"), unit, "total_increasing"),
+        _ => (None, unit, "measurement"),
+    }
+}
+
+/// Identifies the router, preferring its serial number over the configured host.
+pub fn node_id(snapshot: &Snapshot) -> String {
+    object_id(
+        snapshot
+            .info
+            .get(SERIAL_NUMBER)
+            .map_or(&snapshot.metadata.router_host, |i| &i.value),
+    )
+}
+
+/// The `device` block of Home Assistant discovery configs.
+pub fn hass_device(snapshot: &Snapshot) -> Value {
+    let value = |key| {
+        snapshot
+            .info
+            .get(key)
+            .map(|i: &Information| i.value.clone())
+    };
+    json!({
+        "identifiers": [node_id(snapshot)],
+        "manufacturer": "HUAWEI",
+        "name": value(DEVICE_NAME).map_or_else(|| "HUAWEI router".to_string(), |n| format!("HUAWEI {}", n)),
+        "model": value(DEVICE_NAME),
+        "sw_version": value(SOFTWARE_VERSION),
+        "configuration_url": format!("http://{}", snapshot.metadata.router_host),
+    })
+}
+
+impl MqttConfig {
+    fn messages(&self, snapshot: &Snapshot) -> Vec<Message> {
+        let node = node_id(snapshot);
+        let base = format!("{}/{}", self.base_topic, node);
+        let discovery = !self.discovery_prefix.is_empty();
+        let device = hass_device(snapshot);
+        let mut messages = Vec::new();
+
+        let sensor = |key: &str,
+                      name: &str,
+                      state: String,
+                      unit: Option<&str>,
+                      messages: &mut Vec<Message>| {
+            let state_topic = format!("{}/{}/state", base, object_id(key));
+            if discovery {
+                let mut config = json!({
+                    "name": name,
+                    "state_topic": state_topic,
+                    "unique_id": format!("{}_{}", node, object_id(key)),
+                    "object_id": format!("huawei_{}", object_id(key)),
+                    "device": device,
+                });
+                if let Some(unit) = unit {
+                    let (device_class, unit, state_class) = hass_unit(unit);
+                    config["unit_of_measurement"] = json!(unit);
+                    config["state_class"] = json!(state_class);
+                    if let Some(device_class) = device_class {
+                        config["device_class"] = json!(device_class);
+                    }
+                }
+                messages.push(Message {
+                    topic: format!(
+                        "{}/sensor/{}/{}/config",
+                        self.discovery_prefix,
+                        node,
+                        object_id(key)
+                    ),
+                    payload: config.to_string(),
+                    retain: true,
+                });
+            }
+            messages.push(Message {
+                topic: state_topic,
+                payload: state,
+                retain: self.retain,
+            });
+        };
+
+        let mut info: Vec<_> = snapshot.info.iter().collect();
+        info.sort_by_key(|(key, _)| key.as_str());
+        for (key, information) in info {
+            let (state, unit) = match &information.parsed {
+                Some(parsed) => (parsed.value.to_string(), Some(parsed.unit.as_str())),
+                None => (information.value.clone(), None),
+            };
+            sensor(key, &information.label, state, unit, &mut messages);
+        }
+        for (name, help, count) in snapshot.devices.counts() {
+            sensor(name, help, count.to_string(), None, &mut messages);
+        }
+
+        if self.device_trackers {
+            let devices = snapshot
+                .devices
+                .online
+                .iter()
+                .map(|d| (d, "home"))
+                .chain(snapshot.devices.offline.iter().map(|d| (d, "not_home")));
+            for (dev, presence) in devices {
+                let mac = object_id(&dev.mac);
+                let state_topic = format!("{}/device/{}/state", base, mac);
+                if discovery {
+                    messages.push(Message {
+                        topic: format!(
+                            "{}/device_tracker/{}/{}/config",
+                            self.discovery_prefix, node, mac
+                        ),
+                        payload: json!({
+                            "name": dev.name.as_ref().unwrap_or(&dev.mac),
+                            "state_topic": state_topic,
+                            "unique_id": format!("{}_{}", node, mac),
+                            "source_type": "router",
+                            "payload_home": "home",
+                            "payload_not_home": "not_home",
+                            "device": device,
+                        })
+                        .to_string(),
+                        retain: true,
+                    });
+                }
+                messages.push(Message {
+                    topic: state_topic,
+                    payload: presence.to_string(),
+                    retain: self.retain,
+                });
+            }
+        }
+
+        messages
+    }
+}
+
+/// Publishes every information entry and device state to an MQTT broker.
+pub struct MqttSink {
+    config: MqttConfig,
+    qos: QoS,
+}
+
+impl MqttSink {
+    pub fn new(config: MqttConfig) -> Self {
+        let qos = match config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            q => panic!("Invalid MQTT QoS {}", q),
+        };
+        MqttSink { config, qos }
+    }
+}
+
+#[async_trait]
+impl Sink for MqttSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let messages = self.config.messages(snapshot);
+
+        let mut options = MqttOptions::new(
+            self.config.client_id.clone(),
+            self.config.host.clone(),
+            self.config.port,
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &self.config.username {
+            options.set_credentials(
+                username.clone(),
+                self.config.password.clone().unwrap_or_default(),
+            );
+        }
+
+        // A fresh connection per snapshot, all messages are queued before the event loop runs
+        let (client, mut eventloop) = AsyncClient::new(options, messages.len() + 1);
+        for message in messages {
+            trace!("Publishing to {}", message.topic);
+            client
+                .publish(message.topic, self.qos, message.retain, message.payload)
+                .await?;
+        }
+        client.disconnect().await?;
+
+        loop {
+            if let Event::Outgoing(Outgoing::Disconnect) = eventloop.poll().await? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::MqttConfig;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn discovery() {
+        let config: MqttConfig = toml::from_str(r#"host = "localhost""#).unwrap();
+        let messages = config.messages(&Snapshot::example());
+
+        let rsrp = messages
+            .iter()
+            .find(|m| m.topic == "homeassistant/sensor/abc123/rsrp/config")
+            .unwrap();
+        assert!(rsrp.retain);
+        let rsrp: Value = serde_json::from_str(&rsrp.payload).unwrap();
+        assert_eq!(rsrp["unique_id"], "abc123_rsrp");
+        assert_eq!(rsrp["device_class"], "signal_strength");
+        assert_eq!(rsrp["unit_of_measurement"], "dBm");
+        assert_eq!(rsrp["state_topic"], "huawei_metrics/abc123/rsrp/state");
+
+        let state = |topic: &str| {
+            messages
+                .iter()
+                .find(|m| m.topic == topic)
+                .map(|m| m.payload.as_str())
+        };
+        assert_eq!(state("huawei_metrics/abc123/rsrp/state"), Some("-109"));
+        assert_eq!(
+            state("huawei_metrics/abc123/online_devices/state"),
+            Some("1")
+        );
+        assert_eq!(
+            state("huawei_metrics/abc123/device/aa_bb_cc_dd_ee_02/state"),
+            Some("not_home")
+        );
+    }
+}
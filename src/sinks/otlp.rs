@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use prometheus::proto::{MetricFamily, MetricType};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{http::ClientConfig, Sink, SinkError};
+use crate::{metrics, protobuf::Writer, snapshot::Snapshot, DEVICE_NAME, SERIAL_NUMBER};
+
+const GRPC_EXPORT_PATH: &str = "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export";
+/// `AGGREGATION_TEMPORALITY_CUMULATIVE`
+const CUMULATIVE: i64 = 2;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OtlpProtocol {
+    Grpc,
+    HttpProtobuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OtlpConfig {
+    /// `http://localhost:4317` for gRPC, `http://localhost:4318/v1/metrics` for HTTP.
+    pub url: String,
+    #[serde(default = "OtlpConfig::default_protocol")]
+    pub protocol: OtlpProtocol,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Added to the `service.name`, `host.name`, `device.id` and `device.model.name` attributes.
+    #[serde(default)]
+    pub resource_attributes: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl OtlpConfig {
+    fn default_protocol() -> OtlpProtocol {
+        OtlpProtocol::HttpProtobuf
+    }
+}
+
+/// Splits the unit suffix off a metric name, returns the OTel name, UCUM unit and value scale.
+fn instrument(name: &str) -> (String, &'static str, f64) {
+    let name = name.strip_prefix("huawei_metrics_").unwrap_or(name);
+    for (suffix, unit, scale) in [
+        ("_bytes_total", "By", 1.0),
+        ("_bytes", "By", 1.0),
+        ("_gb", "By", 1024.0 * 1024.0 * 1024.0),
+        ("_mb", "By", 1024.0 * 1024.0),
+        ("_kb", "By", 1024.0),
+        ("_b", "By", 1.0),
+        ("_mbps", "bit/s", 1024.0 * 1024.0),
+        ("_dbm", "dBm", 1.0),
+        ("_db", "dB", 1.0),
+        ("_seconds", "s", 1.0),
+        ("_min", "min", 1.0),
+        ("_percent", "%", 1.0),
+        ("_total", "1", 1.0),
+    ] {
+        if let Some(base) = name.strip_suffix(suffix) {
+            return (format!("huawei.{}", base), unit, scale);
+        }
+    }
+    let unit = if name.ends_with("_devices") {
+        "{device}"
+    } else {
+        "1"
+    };
+    (format!("huawei.{}", name), unit, 1.0)
+}
+
+fn key_value(w: &mut Writer, field: u32, key: &str, value: &str) {
+    w.message(field, |kv| {
+        kv.string(1, key);
+        kv.message(2, |any| any.string(1, value));
+    });
+}
+
+/// Encodes the metric families as `ExportMetricsServiceRequest`, counters become monotonic sums.
+fn export_request(
+    families: &[MetricFamily],
+    resource: &BTreeMap<String, String>,
+    time_unix_nano: u64,
+) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.message(1, |resource_metrics| {
+        resource_metrics.message(1, |r| {
+            for (key, value) in resource {
+                key_value(r, 1, key, value);
+            }
+        });
+        resource_metrics.message(2, |scope_metrics| {
+            scope_metrics.message(1, |scope| {
+                scope.string(1, env!("CARGO_PKG_NAME"));
+                scope.string(2, env!("CARGO_PKG_VERSION"));
+            });
+            for family in families {
+                let (name, unit, scale) = instrument(family.get_name());
+                let monotonic = match family.get_field_type() {
+                    MetricType::COUNTER => true,
+                    MetricType::GAUGE => false,
+                    other => {
+                        warn!("Skipping {} of type {:?}", family.get_name(), other);
+                        continue;
+                    }
+                };
+                let data_points = |data: &mut Writer| {
+                    for metric in family.get_metric() {
+                        let value = if monotonic {
+                            metric.get_counter().get_value()
+                        } else {
+                            metric.get_gauge().get_value()
+                        };
+                        data.message(1, |point| {
+                            point.fixed64(3, time_unix_nano);
+                            point.double(4, value * scale);
+                            for pair in metric.get_label() {
+                                key_value(point, 7, pair.get_name(), pair.get_value());
+                            }
+                        });
+                    }
+                };
+                scope_metrics.message(2, |metric| {
+                    metric.string(1, &name);
+                    metric.string(2, family.get_help());
+                    metric.string(3, unit);
+                    if monotonic {
+                        metric.message(7, |sum| {
+                            data_points(sum);
+                            sum.int64(2, CUMULATIVE);
+                            sum.bool(3, true);
+                        });
+                    } else {
+                        metric.message(5, data_points);
+                    }
+                });
+            }
+        });
+    });
+    w.into_bytes()
+}
+
+/// Exports the metrics to an OpenTelemetry collector via OTLP/gRPC or OTLP/HTTP.
+pub struct OtlpSink {
+    client: Client,
+    url: String,
+    config: OtlpConfig,
+}
+
+impl OtlpSink {
+    pub fn new(config: OtlpConfig) -> Self {
+        let (client, url) = match config.protocol {
+            OtlpProtocol::Grpc => (
+                config
+                    .client
+                    .builder()
+                    .http2_prior_knowledge()
+                    .build()
+                    .unwrap(),
+                format!("{}{}", config.url.trim_end_matches('/'), GRPC_EXPORT_PATH),
+            ),
+            OtlpProtocol::HttpProtobuf => (config.client.client(), config.url.clone()),
+        };
+        OtlpSink {
+            client,
+            url,
+            config,
+        }
+    }
+
+    fn resource(&self, snapshot: &Snapshot) -> BTreeMap<String, String> {
+        let mut resource = BTreeMap::new();
+        resource.insert(
+            "service.name".to_string(),
+            env!("CARGO_PKG_NAME").to_string(),
+        );
+        resource.insert(
+            "host.name".to_string(),
+            snapshot.metadata.router_host.clone(),
+        );
+        for (attribute, key) in [
+            ("device.id", SERIAL_NUMBER),
+            ("device.model.name", DEVICE_NAME),
+        ] {
+            if let Some(information) = snapshot.info.get(key) {
+                resource.insert(attribute.to_string(), information.value.clone());
+            }
+        }
+        resource.extend(self.config.resource_attributes.clone());
+        resource
+    }
+}
+
+#[async_trait]
+impl Sink for OtlpSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let message = export_request(
+            &metrics::registry(snapshot).gather(),
+            &self.resource(snapshot),
+            snapshot.metadata.time.timestamp_nanos_opt().unwrap() as u64,
+        );
+        let mut request = self.client.post(&self.url);
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request = match self.config.protocol {
+            OtlpProtocol::Grpc => {
+                // Length-prefixed message without compression
+                let mut body = vec![0];
+                body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+                body.extend_from_slice(&message);
+                request
+                    .header("Content-Type", "application/grpc")
+                    .header("TE", "trailers")
+                    .body(body)
+            }
+            OtlpProtocol::HttpProtobuf => request
+                .header("Content-Type", "application/x-protobuf")
+                .body(message),
+        };
+
+        let response = self
+            .config
+            .client
+            .authorize(request)
+            .send()
+            .await?
+            .error_for_status()?;
+        // Failed calls usually come as trailers-only responses, with the status in the headers
+        if let Some(status) = response.headers().get("grpc-status") {
+            if status != "0" {
+                let message = response
+                    .headers()
+                    .get("grpc-message")
+                    .and_then(|m| m.to_str().ok())
+                    .unwrap_or_default();
+                return Err(format!(
+                    "OTLP export failed with gRPC status {:?}: {}",
+                    status, message
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::instrument;
+
+    #[test]
+    fn ucum_units() {
+        assert_eq!(
+            instrument("huawei_metrics_rsrp_dbm"),
+            ("huawei.rsrp".to_string(), "dBm", 1.0)
+        );
+        assert_eq!(
+            instrument("huawei_metrics_totaldownload_mb"),
+            ("huawei.totaldownload".to_string(), "By", 1048576.0)
+        );
+        assert_eq!(
+            instrument("huawei_metrics_currentdownloadrate_mbps"),
+            ("huawei.currentdownloadrate".to_string(), "bit/s", 1048576.0)
+        );
+        assert_eq!(
+            instrument("huawei_metrics_online_devices"),
+            ("huawei.online_devices".to_string(), "{device}", 1.0)
+        );
+    }
+}
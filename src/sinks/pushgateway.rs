@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use reqwest::{Client, Method, Url};
+use serde::Deserialize;
+
+use super::{http::ClientConfig, Sink, SinkError};
+use crate::{metrics, snapshot::Snapshot};
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushMethod {
+    /// Replaces all metrics of the group.
+    Put,
+    /// Only replaces metrics with the same name.
+    Post,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushgatewayConfig {
+    pub url: String,
+    #[serde(default = "PushgatewayConfig::default_job")]
+    pub job: String,
+    /// Additional grouping labels, e.g. `instance`.
+    #[serde(default)]
+    pub grouping: BTreeMap<String, String>,
+    #[serde(default = "PushgatewayConfig::default_method")]
+    pub method: PushMethod,
+    /// Deletes the group from the pushgateway when the exporter shuts down.
+    #[serde(default)]
+    pub delete_on_shutdown: bool,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl PushgatewayConfig {
+    fn default_job() -> String {
+        "huawei_metrics".to_string()
+    }
+
+    fn default_method() -> PushMethod {
+        PushMethod::Put
+    }
+
+    /// `<url>/metrics/job/<job>/<label>/<value>...`, values the pushgateway can't take in a
+    /// path segment are base64 encoded.
+    fn group_url(&self) -> Result<Url, SinkError> {
+        let mut url = Url::parse(&self.url)?;
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| format!("{} can not be a base URL", self.url))?;
+            segments.pop_if_empty().push("metrics");
+            for (label, value) in std::iter::once(("job", &self.job))
+                .chain(self.grouping.iter().map(|(l, v)| (l.as_str(), v)))
+            {
+                if value.is_empty() || value.contains('/') {
+                    // An empty segment would be dropped, the Pushgateway takes `=` for it
+                    let encoded = if value.is_empty() {
+                        "=".to_string()
+                    } else {
+                        base64::encode_config(value, base64::URL_SAFE)
+                    };
+                    segments.push(&format!("{}@base64", label)).push(&encoded);
+                } else {
+                    segments.push(label).push(value);
+                }
+            }
+        }
+        Ok(url)
+    }
+}
+
+/// Pushes the prometheus registry to a Prometheus Pushgateway.
+pub struct PushgatewaySink {
+    client: Client,
+    config: PushgatewayConfig,
+}
+
+impl PushgatewaySink {
+    pub fn new(config: PushgatewayConfig) -> Self {
+        PushgatewaySink {
+            client: config.client.client(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for PushgatewaySink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let method = match self.config.method {
+            PushMethod::Put => Method::PUT,
+            PushMethod::Post => Method::POST,
+        };
+        let body = metrics::encode_text(&metrics::registry(snapshot));
+        let request = self
+            .client
+            .request(method, self.config.group_url()?)
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(body);
+        self.config
+            .client
+            .authorize(request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        if !self.config.delete_on_shutdown {
+            return Ok(());
+        }
+        let url = self.config.group_url()?;
+        info!("Deleting pushgateway group {}", url);
+        let request = self.client.delete(url);
+        self.config
+            .client
+            .authorize(request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PushgatewayConfig;
+
+    #[test]
+    fn group_url() {
+        let config: PushgatewayConfig = toml::from_str(
+            r#"
+            url = "https://pushgateway.example.com/"
+            grouping = { instance = "home", path = "/var/tmp", site = "" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.group_url().unwrap().as_str(),
+            "https://pushgateway.example.com/metrics/job/huawei_metrics/instance/home/path@base64/L3Zhci90bXA=/site@base64/="
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use prometheus::proto::{MetricFamily, MetricType};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::{fs, sync::Mutex};
+
+use super::{http::ClientConfig, Sink, SinkError};
+use crate::{metrics, protobuf::Writer, snapshot::Snapshot};
+
+#[derive(Debug, Deserialize)]
+pub struct RemoteWriteConfig {
+    /// Remote write endpoint, e.g. `http://prometheus:9090/api/v1/write`.
+    pub url: String,
+    /// Labels added to every series, labels of the series itself take precedence.
+    #[serde(default)]
+    pub external_labels: BTreeMap<String, String>,
+    pub bearer_token: Option<String>,
+    /// Requests which could not be delivered are stored here and sent before the next one.
+    pub queue_dir: Option<PathBuf>,
+    /// Maximum number of queued requests, the oldest are dropped first.
+    #[serde(default = "RemoteWriteConfig::default_max_queued")]
+    pub max_queued: usize,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl RemoteWriteConfig {
+    fn default_max_queued() -> usize {
+        10_000
+    }
+}
+
+/// Encodes the metric families as `prometheus.WriteRequest`, all samples at `timestamp_ms`.
+fn write_request(
+    families: &[MetricFamily],
+    external_labels: &BTreeMap<String, String>,
+    timestamp_ms: i64,
+) -> Vec<u8> {
+    let mut w = Writer::default();
+    for family in families {
+        for metric in family.get_metric() {
+            let mut labels = external_labels.clone();
+            for pair in metric.get_label() {
+                labels.insert(pair.get_name().to_string(), pair.get_value().to_string());
+            }
+            labels.insert("__name__".to_string(), family.get_name().to_string());
+            let value = match family.get_field_type() {
+                MetricType::COUNTER => metric.get_counter().get_value(),
+                MetricType::GAUGE => metric.get_gauge().get_value(),
+                MetricType::UNTYPED => metric.get_untyped().get_value(),
+                other => {
+                    warn!("Skipping {} of type {:?}", family.get_name(), other);
+                    continue;
+                }
+            };
+            w.message(1, |series| {
+                for (name, value) in &labels {
+                    series.message(1, |label| {
+                        label.string(1, name);
+                        label.string(2, value);
+                    });
+                }
+                series.message(2, |sample| {
+                    sample.double(1, value);
+                    sample.int64(2, timestamp_ms);
+                });
+            });
+        }
+    }
+    for family in families {
+        let metric_type = match family.get_field_type() {
+            MetricType::COUNTER => 1,
+            MetricType::GAUGE => 2,
+            _ => 0,
+        };
+        w.message(3, |metadata| {
+            metadata.int64(1, metric_type);
+            metadata.string(2, family.get_name());
+            metadata.string(4, family.get_help());
+        });
+    }
+    w.into_bytes()
+}
+
+/// Pushes the metrics to a Prometheus remote write endpoint, optionally queueing them on disk.
+pub struct RemoteWriteSink {
+    client: Client,
+    config: RemoteWriteConfig,
+    /// Keeps concurrent sends from delivering the queue twice.
+    queue_lock: Mutex<()>,
+}
+
+enum Delivery {
+    Sent,
+    /// The endpoint refused the request, sending it again won't help.
+    Rejected(StatusCode),
+}
+
+impl RemoteWriteSink {
+    pub fn new(config: RemoteWriteConfig) -> Self {
+        if let Some(dir) = &config.queue_dir {
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|e| panic!("Unable to create {}: {:#}", dir.display(), e));
+        }
+        RemoteWriteSink {
+            client: config.client.client(),
+            config,
+            queue_lock: Mutex::new(()),
+        }
+    }
+
+    async fn deliver(&self, body: Vec<u8>) -> Result<Delivery, SinkError> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header("Content-Type", "application/x-protobuf")
+            .header("Content-Encoding", "snappy")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(body);
+        if let Some(token) = &self.config.bearer_token {
+            request = request.bearer_auth(token);
+        }
+        let response = self.config.client.authorize(request).send().await?;
+        let status = response.status();
+        if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+            Ok(Delivery::Rejected(status))
+        } else {
+            response.error_for_status()?;
+            Ok(Delivery::Sent)
+        }
+    }
+
+    /// Queued request files, oldest first.
+    async fn queued(&self, dir: &Path) -> Result<Vec<PathBuf>, SinkError> {
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "snappy") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[async_trait]
+impl Sink for RemoteWriteSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let timestamp_ms = snapshot.metadata.time.timestamp_millis();
+        let request = write_request(
+            &metrics::registry(snapshot).gather(),
+            &self.config.external_labels,
+            timestamp_ms,
+        );
+        let body = snap::raw::Encoder::new().compress_vec(&request)?;
+
+        let dir = match &self.config.queue_dir {
+            Some(dir) => dir,
+            None => {
+                if let Delivery::Rejected(status) = self.deliver(body).await? {
+                    return Err(format!("Remote write rejected with {}", status).into());
+                }
+                return Ok(());
+            }
+        };
+
+        let _guard = self.queue_lock.lock().await;
+        // Named by timestamp, so a retried snapshot replaces its own queue entry
+        fs::write(dir.join(format!("{:020}.snappy", timestamp_ms)), body).await?;
+
+        let mut queued = self.queued(dir).await?;
+        if queued.len() > self.config.max_queued {
+            let dropped = queued.len() - self.config.max_queued;
+            warn!("Dropping {} queued remote write requests", dropped);
+            for path in queued.drain(..dropped) {
+                fs::remove_file(path).await?;
+            }
+        }
+        for path in queued {
+            trace!("Sending queued remote write request {}", path.display());
+            if let Delivery::Rejected(status) = self.deliver(fs::read(&path).await?).await? {
+                warn!(
+                    "Remote write rejected {} with {}, dropping it",
+                    path.display(),
+                    status
+                );
+            }
+            fs::remove_file(path).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prometheus::{IntGauge, Opts, Registry};
+
+    use super::write_request;
+
+    #[test]
+    fn encoding() {
+        let r = Registry::new();
+        let gauge = IntGauge::with_opts(Opts::new("g", "h").const_label("a", "b")).unwrap();
+        gauge.set(1);
+        r.register(Box::new(gauge)).unwrap();
+        let mut external = BTreeMap::new();
+        external.insert("site".to_string(), "x".to_string());
+        external.insert("a".to_string(), "overridden".to_string());
+
+        let label = |name: &str, value: &str| {
+            [
+                &[
+                    0x0a,
+                    (name.len() + value.len() + 4) as u8,
+                    0x0a,
+                    name.len() as u8,
+                ][..],
+                name.as_bytes(),
+                &[0x12, value.len() as u8],
+                value.as_bytes(),
+            ]
+            .concat()
+        };
+        let labels = [label("__name__", "g"), label("a", "b"), label("site", "x")].concat();
+        let sample = [&[0x12, 0x0b, 0x09][..], &1f64.to_le_bytes(), &[0x10, 0x02]].concat();
+        let series = [
+            &[0x0a, (labels.len() + sample.len()) as u8][..],
+            &labels,
+            &sample,
+        ]
+        .concat();
+        let metadata = [
+            &[0x1a, 0x08, 0x08, 0x02, 0x12, 0x01][..],
+            b"g",
+            &[0x22, 0x01],
+            b"h",
+        ]
+        .concat();
+
+        assert_eq!(
+            write_request(&r.gather(), &external, 2),
+            [series, metadata].concat()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::net::UdpSocket;
+
+use super::{
+    graphite::{points, render_path, serial},
+    Sink, SinkError,
+};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize)]
+pub struct StatsdConfig {
+    pub host: String,
+    #[serde(default = "StatsdConfig::default_port")]
+    pub port: u16,
+    /// Metric name template, see the graphite sink.
+    #[serde(default = "StatsdConfig::default_template")]
+    pub template: String,
+    /// Appends DogStatsD tags for the router, serial number and `tags`.
+    #[serde(default)]
+    pub dogstatsd: bool,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl StatsdConfig {
+    fn default_port() -> u16 {
+        8125
+    }
+
+    fn default_template() -> String {
+        "huawei.{group}.{key}".to_string()
+    }
+
+    fn lines(&self, snapshot: &Snapshot) -> Vec<String> {
+        let tags = if self.dogstatsd {
+            let mut tags = vec![
+                format!("router:{}", snapshot.metadata.router_host),
+                format!("serial:{}", serial(snapshot)),
+            ];
+            tags.extend(self.tags.iter().map(|(k, v)| format!("{}:{}", k, v)));
+            format!("|#{}", tags.join(","))
+        } else {
+            String::new()
+        };
+
+        let mut lines = Vec::new();
+        for point in points(snapshot) {
+            let name = render_path(&self.template, snapshot, &point);
+            // Plain StatsD treats signed gauges as deltas, reset to zero first
+            if !self.dogstatsd && point.value < 0.0 {
+                lines.push(format!("{}:0|g", name));
+            }
+            lines.push(format!("{}:{}|g{}", name, point.value, tags));
+        }
+        lines
+    }
+}
+
+/// Sends the information values and device counts as StatsD gauges over UDP.
+pub struct StatsdSink {
+    config: StatsdConfig,
+}
+
+impl StatsdSink {
+    pub fn new(config: StatsdConfig) -> Self {
+        StatsdSink { config }
+    }
+}
+
+#[async_trait]
+impl Sink for StatsdSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket
+            .connect((self.config.host.as_str(), self.config.port))
+            .await?;
+        // One datagram per metric keeps every packet well below the MTU
+        for line in self.config.lines(snapshot) {
+            socket.send(line.as_bytes()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsdConfig;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn gauges() {
+        let snapshot = Snapshot::example();
+        let config: StatsdConfig = toml::from_str(r#"host = "localhost""#).unwrap();
+        let lines = config.lines(&snapshot);
+        assert_eq!(
+            lines[..2],
+            ["huawei.signal.rsrp:0|g", "huawei.signal.rsrp:-109|g"]
+        );
+
+        let config: StatsdConfig = toml::from_str(
+            r#"
+            host = "localhost"
+            dogstatsd = true
+            tags = { site = "cabin" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.lines(&snapshot)[0],
+            "huawei.signal.rsrp:-109|g|#router:192.168.8.1,serial:abc123,site:cabin"
+        );
+    }
+}
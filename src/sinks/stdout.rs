@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{Format, Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize)]
+pub struct StdoutConfig {
+    #[serde(default)]
+    pub format: Format,
+}
+
+pub struct StdoutSink {
+    format: Format,
+}
+
+impl StdoutSink {
+    pub fn new(config: StdoutConfig) -> Self {
+        StdoutSink {
+            format: config.format,
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for StdoutSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        println!("{}", self.format.render(snapshot));
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use async_trait::async_trait;
+use chrono::SecondsFormat;
+use serde::Deserialize;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpStream, UdpSocket},
+};
+
+use super::{Sink, SinkError};
+use crate::{
+    events::{Event, EventKind},
+    snapshot::Snapshot,
+};
+
+/// Structured data ID of the event fields, 32473 is the enterprise number reserved for examples.
+const SD_ID: &str = "huawei@32473";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogProtocol {
+    /// A datagram per message.
+    #[default]
+    Udp,
+    /// Octet-counted messages as in RFC 6587.
+    Tcp,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Facility {
+    User,
+    #[default]
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    fn code(&self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyslogConfig {
+    pub host: String,
+    #[serde(default = "SyslogConfig::default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub protocol: SyslogProtocol,
+    #[serde(default)]
+    pub facility: Facility,
+    #[serde(default = "SyslogConfig::default_app_name")]
+    pub app_name: String,
+}
+
+impl SyslogConfig {
+    fn default_port() -> u16 {
+        514
+    }
+
+    fn default_app_name() -> String {
+        "huawei-metrics".to_string()
+    }
+
+    /// An RFC 5424 message with the router as host name and the event type as message ID.
+    fn message(&self, event: &Event) -> String {
+        let severity = match event.kind {
+            EventKind::ScrapeFailed => 4,
+            EventKind::RouterRestarted | EventKind::CellChanged => 5,
+            EventKind::DeviceJoined | EventKind::DeviceLeft => 6,
+        };
+        let mut data = format!("[{} router=\"{}\"", SD_ID, escape(&event.router));
+        for (key, value) in &event.fields {
+            data.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        data.push(']');
+        format!(
+            "<{}>1 {} {} {} - {} {} {}",
+            self.facility.code() * 8 + severity,
+            event.time.to_rfc3339_opts(SecondsFormat::Millis, false),
+            event.router,
+            self.app_name,
+            event.kind.name(),
+            data,
+            event.message
+        )
+    }
+}
+
+/// Escapes structured data parameter values.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace(']', r"\]")
+}
+
+/// Ships events to a syslog server, snapshots are left to the other sinks.
+pub struct SyslogSink {
+    config: SyslogConfig,
+}
+
+impl SyslogSink {
+    pub fn new(config: SyslogConfig) -> Self {
+        SyslogSink { config }
+    }
+}
+
+#[async_trait]
+impl Sink for SyslogSink {
+    async fn send(&self, _snapshot: &Snapshot) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    async fn events(&self, events: &[Event]) -> Result<(), SinkError> {
+        let address = (self.config.host.as_str(), self.config.port);
+        let messages = events.iter().map(|e| self.config.message(e));
+        match self.config.protocol {
+            SyslogProtocol::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(address).await?;
+                for message in messages {
+                    socket.send(message.as_bytes()).await?;
+                }
+            }
+            SyslogProtocol::Tcp => {
+                let mut stream = TcpStream::connect(address).await?;
+                for message in messages {
+                    stream
+                        .write_all(format!("{} {}", message.len(), message).as_bytes())
+                        .await?;
+                }
+                stream.shutdown().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::SyslogConfig;
+    use crate::events::Event;
+
+    #[test]
+    fn rfc5424() {
+        let config: SyslogConfig = toml::from_str(
+            r#"
+            host = "localhost"
+            facility = "local0"
+            "#,
+        )
+        .unwrap();
+        let time = Local.with_ymd_and_hms(2021, 6, 1, 12, 0, 0).unwrap();
+        let event = Event::scrape_failed(time, "192.168.8.1", r#"element "login_btn" not found"#);
+        assert_eq!(
+            config.message(&event),
+            format!(
+                "<132>1 {} 192.168.8.1 huawei-metrics - scrape_failed \
+                [huawei@32473 router=\"192.168.8.1\" reason=\"element \\\"login_btn\\\" not found\"] \
+                Scraping the router failed: element \"login_btn\" not found",
+                time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false)
+            )
+        );
+    }
+}
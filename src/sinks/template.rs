@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{to_value, Value};
+use tera::{Context, Tera};
+
+use super::{file::write_atomic, http::ClientConfig, Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Deserialize)]
+pub struct TemplateConfig {
+    /// Tera template file, autoescaped for HTML when named `.html`, `.htm` or `.xml`.
+    pub template: PathBuf,
+    /// File the output replaces, printed to stdout if neither this nor `url` is set.
+    pub path: Option<PathBuf>,
+    /// Endpoint the output is POSTed to.
+    pub url: Option<String>,
+    #[serde(default = "TemplateConfig::default_content_type")]
+    pub content_type: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(flatten)]
+    pub client: ClientConfig,
+}
+
+impl TemplateConfig {
+    fn default_content_type() -> String {
+        "text/plain; charset=utf-8".to_string()
+    }
+}
+
+/// Tera only names the failing template, the reason is in its sources.
+fn describe(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// What templates are rendered against: the sections of the JSON output with the
+/// information entries under `info` instead of at the top level.
+fn context(snapshot: &Snapshot) -> Context {
+    let mut json = snapshot.to_json();
+    for key in snapshot.info.keys() {
+        json.remove(key);
+    }
+    json.insert("info".to_string(), to_value(&snapshot.info).unwrap());
+    Context::from_serialize(Value::Object(json)).unwrap()
+}
+
+/// Renders a user supplied template per snapshot, for outputs none of the formats fit.
+pub struct TemplateSink {
+    tera: Tera,
+    name: String,
+    client: Option<Client>,
+    config: TemplateConfig,
+}
+
+impl TemplateSink {
+    pub fn new(config: TemplateConfig) -> Self {
+        let source = fs::read_to_string(&config.template).unwrap_or_else(|e| {
+            panic!(
+                "Unable to read template {}: {}",
+                config.template.display(),
+                e
+            )
+        });
+        let name = config
+            .template
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let mut tera = Tera::default();
+        tera.add_raw_template(&name, &source)
+            .unwrap_or_else(|e| panic!("Invalid template: {}", describe(&e)));
+        TemplateSink {
+            tera,
+            name,
+            client: config.url.as_ref().map(|_| config.client.client()),
+            config,
+        }
+    }
+
+    fn render(&self, snapshot: &Snapshot) -> Result<String, SinkError> {
+        Ok(self
+            .tera
+            .render(&self.name, &context(snapshot))
+            .map_err(|e| describe(&e))?)
+    }
+}
+
+#[async_trait]
+impl Sink for TemplateSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let output = self.render(snapshot)?;
+        if let Some(path) = &self.config.path {
+            trace!("Outputting to {}", path.display());
+            write_atomic(path, &output).await?;
+        }
+        if let (Some(url), Some(client)) = (&self.config.url, &self.client) {
+            let mut request = client
+                .post(url)
+                .header("Content-Type", &self.config.content_type)
+                .body(output.clone());
+            for (name, value) in &self.config.headers {
+                request = request.header(name.as_str(), value.as_str());
+            }
+            self.config
+                .client
+                .authorize(request)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        if self.config.path.is_none() && self.config.url.is_none() {
+            // Templates don't have to end with a newline, which line buffered stdout waits for
+            print!("{}", output);
+            io::stdout().flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{TemplateConfig, TemplateSink};
+    use crate::{snapshot::Snapshot, testing::temp_dir};
+
+    fn sink(name: &str, template: &str) -> TemplateSink {
+        let dir = temp_dir("template");
+        let path = dir.path().join(name);
+        fs::write(&path, template).unwrap();
+        let config: TemplateConfig =
+            toml::from_str(&format!("template = {:?}", path.display().to_string())).unwrap();
+        TemplateSink::new(config)
+    }
+
+    #[test]
+    fn renders_snapshot() {
+        let snapshot = Snapshot::example();
+        let status = sink(
+            "status.txt",
+            "SINR {{ info.sinr.parsed.value }}{{ info.sinr.parsed.unit }}, \
+            {{ devices.online | length }} online{% for d in devices.online %} {{ d.name }}{% endfor %} \
+            ({{ metadata.router_host }})",
+        );
+        assert_eq!(
+            status.render(&snapshot).unwrap(),
+            "SINR -1dB, 1 online Laptop (192.168.8.1)"
+        );
+
+        let payload = sink(
+            "payload.json",
+            r#"{"serial": {{ info.serialNumber.value | json_encode() }}, "offline": {{ devices.offline | map(attribute="mac") | json_encode() }}}"#,
+        );
+        assert_eq!(
+            payload.render(&snapshot).unwrap(),
+            r#"{"serial": "ABC123", "offline": ["AA:BB:CC:DD:EE:02"]}"#
+        );
+
+        let broken = sink("broken.txt", "{{ info.missing.value }}");
+        assert!(broken
+            .render(&snapshot)
+            .unwrap_err()
+            .to_string()
+            .contains("info.missing.value"));
+    }
+}
@@ -0,0 +1,265 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::{Sink, SinkError};
+use crate::snapshot::Snapshot;
+
+const HEADER: &[u8] = b"ZBXD\x01";
+/// Responses are a short status, anything longer is not from a Zabbix server.
+const MAX_FRAME_LENGTH: u64 = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ZabbixConfig {
+    /// Zabbix server or proxy accepting trapper items.
+    pub server: String,
+    #[serde(default = "ZabbixConfig::default_port")]
+    pub port: u16,
+    /// Name of the host in Zabbix the items belong to.
+    pub host: String,
+    #[serde(default = "ZabbixConfig::default_key_prefix")]
+    pub key_prefix: String,
+    /// Sends low-level discovery data for the information keys and devices.
+    #[serde(default = "ZabbixConfig::default_discovery")]
+    pub discovery: bool,
+}
+
+impl ZabbixConfig {
+    fn default_port() -> u16 {
+        10051
+    }
+
+    fn default_key_prefix() -> String {
+        "huawei".to_string()
+    }
+
+    fn default_discovery() -> bool {
+        true
+    }
+
+    /// Low-level discovery values, `<prefix>.info.discovery` and `<prefix>.device.discovery`.
+    fn discovery(&self, snapshot: &Snapshot) -> Vec<(String, String)> {
+        let mut info: Vec<_> = snapshot.info.iter().collect();
+        info.sort_by_key(|(key, _)| key.as_str());
+        let info: Vec<_> = info
+            .into_iter()
+            .map(|(key, information)| {
+                json!({
+                    "{#KEY}": key,
+                    "{#LABEL}": information.label,
+                    "{#UNIT}": information.parsed.as_ref().map_or("", |p| p.unit.as_str()),
+                    "{#NUMERIC}": information.parsed.is_some() as u8,
+                })
+            })
+            .collect();
+        let devices: Vec<_> = snapshot
+            .devices
+            .online
+            .iter()
+            .map(|dev| {
+                json!({
+                    "{#MAC}": dev.mac,
+                    "{#NAME}": dev.name.as_ref().unwrap_or(&dev.mac),
+                })
+            })
+            .collect();
+        vec![
+            (
+                format!("{}.info.discovery", self.key_prefix),
+                Value::from(info).to_string(),
+            ),
+            (
+                format!("{}.device.discovery", self.key_prefix),
+                Value::from(devices).to_string(),
+            ),
+        ]
+    }
+
+    /// Item values, `<prefix>.info[<key>]`, `<prefix>.devices[<count>]` and `<prefix>.device.online[<mac>]`.
+    fn items(&self, snapshot: &Snapshot) -> Vec<(String, String)> {
+        let prefix = &self.key_prefix;
+        let mut items = if self.discovery {
+            self.discovery(snapshot)
+        } else {
+            Vec::new()
+        };
+        for (key, information) in &snapshot.info {
+            let value = match &information.parsed {
+                Some(parsed) => parsed.value.to_string(),
+                None => information.value.clone(),
+            };
+            items.push((format!("{}.info[{}]", prefix, key), value));
+        }
+        for (name, _, count) in snapshot.devices.counts() {
+            items.push((format!("{}.devices[{}]", prefix, name), count.to_string()));
+        }
+        let devices = snapshot
+            .devices
+            .online
+            .iter()
+            .map(|d| (d, 1))
+            .chain(snapshot.devices.offline.iter().map(|d| (d, 0)));
+        for (dev, online) in devices {
+            items.push((
+                format!("{}.device.online[{}]", prefix, dev.mac),
+                online.to_string(),
+            ));
+        }
+        items
+    }
+
+    fn request(&self, snapshot: &Snapshot) -> Value {
+        let clock = snapshot.metadata.time.timestamp();
+        let data: Vec<_> = self
+            .items(snapshot)
+            .into_iter()
+            .map(|(key, value)| {
+                json!({ "host": self.host, "key": key, "value": value, "clock": clock })
+            })
+            .collect();
+        json!({ "request": "sender data", "data": data, "clock": clock })
+    }
+}
+
+/// Frames `payload` with the Zabbix protocol header and length.
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut message = HEADER.to_vec();
+    message.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>, SinkError> {
+    let mut header = [0; 13];
+    stream.read_exact(&mut header).await?;
+    if &header[..5] != HEADER {
+        return Err("Invalid Zabbix response header".into());
+    }
+    let mut length = [0; 8];
+    length.copy_from_slice(&header[5..]);
+    let length = u64::from_le_bytes(length);
+    if length > MAX_FRAME_LENGTH {
+        return Err(format!("Zabbix response of {} bytes is too long", length).into());
+    }
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload).await?;
+    Ok(payload)
+}
+
+/// Sends all values as trapper items using the Zabbix sender protocol.
+pub struct ZabbixSink {
+    config: ZabbixConfig,
+}
+
+impl ZabbixSink {
+    pub fn new(config: ZabbixConfig) -> Self {
+        ZabbixSink { config }
+    }
+}
+
+#[async_trait]
+impl Sink for ZabbixSink {
+    async fn send(&self, snapshot: &Snapshot) -> Result<(), SinkError> {
+        let request = self.config.request(snapshot).to_string();
+        let mut stream =
+            TcpStream::connect((self.config.server.as_str(), self.config.port)).await?;
+        stream.write_all(&frame(request.as_bytes())).await?;
+
+        let response: Value = serde_json::from_slice(&read_frame(&mut stream).await?)?;
+        let info = response["info"].as_str().unwrap_or_default();
+        if response["response"] != "success" {
+            return Err(format!("Zabbix server refused the data: {}", info).into());
+        }
+        // Items which don't exist (yet) are counted as failed, which is expected before discovery ran
+        if !info.contains("failed: 0;") {
+            warn!("Zabbix did not process all items: {}", info);
+        } else {
+            trace!("Zabbix processed the items: {}", info);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use super::{frame, read_frame, ZabbixConfig, ZabbixSink, HEADER};
+    use crate::{sinks::Sink, snapshot::Snapshot};
+
+    #[tokio::test]
+    async fn sender_protocol() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request: Value =
+                serde_json::from_slice(&read_frame(&mut stream).await.unwrap()).unwrap();
+            let response = json!({
+                "response": "success",
+                "info": "processed: 1; failed: 0; total: 1; seconds spent: 0.000055"
+            });
+            stream
+                .write_all(&frame(response.to_string().as_bytes()))
+                .await
+                .unwrap();
+            request
+        });
+
+        let config: ZabbixConfig = toml::from_str(&format!(
+            "server = \"127.0.0.1\"\nport = {}\nhost = \"router\"",
+            port
+        ))
+        .unwrap();
+        ZabbixSink::new(config)
+            .send(&Snapshot::example())
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert_eq!(request["request"], "sender data");
+        let data = request["data"].as_array().unwrap();
+        let value = |key: &str| {
+            data.iter()
+                .find(|item| item["key"] == key)
+                .map(|item| item["value"].clone())
+        };
+        assert_eq!(value("huawei.info[rsrp]"), Some(json!("-109")));
+        assert_eq!(value("huawei.devices[online_devices]"), Some(json!("1")));
+        assert_eq!(
+            value("huawei.device.online[AA:BB:CC:DD:EE:02]"),
+            Some(json!("0"))
+        );
+        let devices: Value =
+            serde_json::from_str(value("huawei.device.discovery").unwrap().as_str().unwrap())
+                .unwrap();
+        assert_eq!(
+            devices,
+            json!([{ "{#MAC}": "AA:BB:CC:DD:EE:01", "{#NAME}": "Laptop" }])
+        );
+        assert_eq!(data[0]["host"], "router");
+    }
+
+    #[tokio::test]
+    async fn oversized_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut header = HEADER.to_vec();
+            header.extend_from_slice(&u64::MAX.to_le_bytes());
+            stream.write_all(&header).await.unwrap();
+        });
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        assert!(read_frame(&mut stream)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("too long"));
+    }
+}
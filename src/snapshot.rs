@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, to_value, Map, Value};
+
+use crate::{
+    counters::TrafficTotals,
+    quota::QuotaStatus,
+    statistics::Statistics,
+    usage::{AverageRates, UsageSummary},
+    DeviceOverview, Information,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Metadata {
+    pub time: DateTime<Local>,
+    pub router_host: String,
+    pub exporter_version: String,
+}
+
+/// Everything gathered in one run, handed to every configured sink.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub metadata: Metadata,
+    pub info: HashMap<String, Information>,
+    pub devices: DeviceOverview,
+    /// Missing when the router has no statistics page.
+    pub statistics: Option<Statistics>,
+    pub traffic: Option<TrafficTotals>,
+    pub average_rates: Option<AverageRates>,
+    pub usage: Option<UsageSummary>,
+    pub quota: Option<QuotaStatus>,
+}
+
+impl Snapshot {
+    /// The JSON document printed by `-f json`, information entries are top level keys.
+    pub fn to_json(&self) -> Map<String, Value> {
+        let mut info_map = Map::new();
+
+        info_map.insert("metadata".to_string(), to_value(&self.metadata).unwrap());
+        info_map.insert("devices".to_string(), to_value(&self.devices).unwrap());
+        if let Some(statistics) = &self.statistics {
+            info_map.insert("statistics".to_string(), to_value(statistics).unwrap());
+        }
+        if let Some(totals) = &self.traffic {
+            info_map.insert("traffic".to_string(), to_value(totals).unwrap());
+        }
+        if let Some(rates) = &self.average_rates {
+            info_map.insert("average_rates".to_string(), to_value(rates).unwrap());
+        }
+        if let Some(quota) = &self.quota {
+            info_map.insert("quota".to_string(), to_value(quota).unwrap());
+        }
+        if let Some(usage) = &self.usage {
+            info_map.insert("usage".to_string(), to_value(usage).unwrap());
+        }
+        for (k, v) in &self.info {
+            let old_data = info_map.insert(k.to_owned(), to_value(v).unwrap());
+            if old_data.is_some() {
+                error!(
+                    "Somehow overwrote data when copying one map to another: {}",
+                    k
+                );
+            }
+        }
+
+        info_map
+    }
+
+    /// The inverse of `to_json`, every key which is not a known section is an information entry.
+    pub fn from_json(mut json: Map<String, Value>) -> serde_json::Result<Self> {
+        let mut section = |key: &str| json.remove(key).unwrap_or(Value::Null);
+        let metadata = from_value(section("metadata"))?;
+        let devices = from_value(section("devices"))?;
+        let statistics = from_value(section("statistics"))?;
+        let traffic = from_value(section("traffic"))?;
+        let average_rates = from_value(section("average_rates"))?;
+        let quota = from_value(section("quota"))?;
+        let usage = from_value(section("usage"))?;
+        Ok(Snapshot {
+            metadata,
+            info: from_value(Value::Object(json))?,
+            devices,
+            statistics,
+            traffic,
+            average_rates,
+            usage,
+            quota,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Snapshot {
+    /// A small snapshot for testing sinks.
+    pub fn example() -> Self {
+        use chrono::TimeZone;
+        use serde_json::json;
+
+        let info = json!({
+            "serialNumber": {
+                "label_id": "deviceinformation.serialNumber",
+                "label": "Serial number",
+                "value_id": "di-serialNumber",
+                "value": "ABC123",
+                "parsed": null,
+                "hidden": false
+            },
+            "sinr": {
+                "label_id": "deviceinformation.sinr",
+                "label": "SINR",
+                "value_id": "di-sinr",
+                "value": "-1dB",
+                "parsed": { "value": -1.0, "unit": "dB" },
+                "hidden": false
+            },
+            "rsrp": {
+                "label_id": "deviceinformation.rsrp",
+                "label": "RSRP",
+                "value_id": "di-rsrp",
+                "value": "-109dBm",
+                "parsed": { "value": -109.0, "unit": "dBm" },
+                "hidden": false
+            },
+            "totaldownload": {
+                "label_id": "deviceinformation.totaldownload",
+                "label": "Total download traffic",
+                "value_id": "deviceinformation_totaldownload",
+                "value": "2048MB",
+                "parsed": { "value": 2048.0, "unit": "MB" },
+                "hidden": true
+            }
+        });
+        let devices = json!({
+            "online": [{
+                "connection": { "wifi": "5GHz" },
+                "name": "Laptop",
+                "ips": ["192.168.8.100"],
+                "uptime": { "countdown": false, "minutes": 90 },
+                "leasetime": { "countdown": true, "minutes": 1200 },
+                "mac": "AA:BB:CC:DD:EE:01"
+            }],
+            "offline": [{
+                "connection": null,
+                "name": null,
+                "ips": null,
+                "uptime": null,
+                "leasetime": null,
+                "mac": "AA:BB:CC:DD:EE:02"
+            }]
+        });
+        let statistics = json!({
+            "month_download": { "value": 1.5, "unit": "GB" },
+            "month_upload": null,
+            "month_duration_seconds": 3600,
+            "plan": { "start_day": 1, "limit": null, "warning_threshold_percent": 90.0 }
+        });
+
+        Snapshot {
+            metadata: Metadata {
+                time: Local.with_ymd_and_hms(2021, 6, 1, 12, 0, 0).unwrap(),
+                router_host: "192.168.8.1".to_string(),
+                exporter_version: "0.1.0".to_string(),
+            },
+            info: from_value(info).unwrap(),
+            devices: from_value(devices).unwrap(),
+            statistics: Some(from_value(statistics).unwrap()),
+            traffic: None,
+            average_rates: None,
+            usage: None,
+            quota: None,
+        }
+    }
+}
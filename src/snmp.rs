@@ -0,0 +1,294 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{snapshot::Snapshot, DEVICE_NAME, SERIAL_NUMBER, SOFTWARE_VERSION};
+
+/// `enterprises.32473`, the enterprise number reserved for documentation and examples.
+pub const BASE_OID: [u32; 7] = [1, 3, 6, 1, 4, 1, 32473];
+pub const MIB_NAME: &str = "HUAWEI-METRICS-MIB";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax {
+    DisplayString,
+    /// Signal values in tenths of their unit.
+    Integer32,
+    Gauge32,
+    Counter32,
+    Counter64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i32),
+    Gauge(u32),
+    Counter32(u32),
+    Counter64(u64),
+}
+
+/// One scalar of the MIB, served as `BASE_OID.1.<sub_id>.0`.
+pub struct Object {
+    pub sub_id: u32,
+    pub name: &'static str,
+    pub syntax: Syntax,
+    pub units: Option<&'static str>,
+    pub description: &'static str,
+    value: fn(&Snapshot) -> Option<Value>,
+}
+
+fn info(snapshot: &Snapshot, key: &str) -> Option<Value> {
+    Some(Value::String(snapshot.info.get(key)?.value.clone()))
+}
+
+fn tenths(snapshot: &Snapshot, key: &str) -> Option<Value> {
+    let parsed = snapshot.info.get(key)?.parsed.as_ref()?;
+    Some(Value::Integer((parsed.value * 10.0).round() as i32))
+}
+
+fn traffic(snapshot: &Snapshot, download: bool) -> Option<Value> {
+    let bytes = match &snapshot.traffic {
+        Some(totals) if download => totals.total.download,
+        Some(totals) => totals.total.upload,
+        None => {
+            let key = if download {
+                "totaldownload"
+            } else {
+                "totalupload"
+            };
+            snapshot.info.get(key)?.bytes()?
+        }
+    };
+    Some(Value::Counter64(bytes))
+}
+
+fn devices(snapshot: &Snapshot, index: usize) -> Option<Value> {
+    Some(Value::Gauge(snapshot.devices.counts()[index].2 as u32))
+}
+
+pub const OBJECTS: &[Object] = &[
+    Object {
+        sub_id: 1,
+        name: "hwmRouterHost",
+        syntax: Syntax::DisplayString,
+        units: None,
+        description: "Host name or address of the router the values were scraped from.",
+        value: |s| Some(Value::String(s.metadata.router_host.clone())),
+    },
+    Object {
+        sub_id: 2,
+        name: "hwmSerialNumber",
+        syntax: Syntax::DisplayString,
+        units: None,
+        description: "Serial number of the router.",
+        value: |s| info(s, SERIAL_NUMBER),
+    },
+    Object {
+        sub_id: 3,
+        name: "hwmDeviceName",
+        syntax: Syntax::DisplayString,
+        units: None,
+        description: "Model name of the router.",
+        value: |s| info(s, DEVICE_NAME),
+    },
+    Object {
+        sub_id: 4,
+        name: "hwmSoftwareVersion",
+        syntax: Syntax::DisplayString,
+        units: None,
+        description: "Software version of the router.",
+        value: |s| info(s, SOFTWARE_VERSION),
+    },
+    Object {
+        sub_id: 5,
+        name: "hwmLastUpdate",
+        syntax: Syntax::DisplayString,
+        units: None,
+        description: "Time of the scrape the values are from, in RFC 3339 format.",
+        value: |s| Some(Value::String(s.metadata.time.to_rfc3339())),
+    },
+    Object {
+        sub_id: 10,
+        name: "hwmRsrp",
+        syntax: Syntax::Integer32,
+        units: Some("0.1 dBm"),
+        description: "Reference signal received power.",
+        value: |s| tenths(s, "rsrp"),
+    },
+    Object {
+        sub_id: 11,
+        name: "hwmRsrq",
+        syntax: Syntax::Integer32,
+        units: Some("0.1 dB"),
+        description: "Reference signal received quality.",
+        value: |s| tenths(s, "rsrq"),
+    },
+    Object {
+        sub_id: 12,
+        name: "hwmRssi",
+        syntax: Syntax::Integer32,
+        units: Some("0.1 dBm"),
+        description: "Received signal strength indicator.",
+        value: |s| tenths(s, "rssi"),
+    },
+    Object {
+        sub_id: 13,
+        name: "hwmSinr",
+        syntax: Syntax::Integer32,
+        units: Some("0.1 dB"),
+        description: "Signal to interference plus noise ratio.",
+        value: |s| tenths(s, "sinr"),
+    },
+    Object {
+        sub_id: 20,
+        name: "hwmDownloadBytes",
+        syntax: Syntax::Counter64,
+        units: Some("bytes"),
+        description:
+            "Total download traffic, continued across router restarts when a state file is used.",
+        value: |s| traffic(s, true),
+    },
+    Object {
+        sub_id: 21,
+        name: "hwmUploadBytes",
+        syntax: Syntax::Counter64,
+        units: Some("bytes"),
+        description:
+            "Total upload traffic, continued across router restarts when a state file is used.",
+        value: |s| traffic(s, false),
+    },
+    Object {
+        sub_id: 22,
+        name: "hwmRouterRestarts",
+        syntax: Syntax::Counter32,
+        units: None,
+        description: "Number of detected router restarts or traffic statistic resets.",
+        value: |s| Some(Value::Counter32(s.traffic.as_ref()?.restarts as u32)),
+    },
+    Object {
+        sub_id: 30,
+        name: "hwmOnlineDevices",
+        syntax: Syntax::Gauge32,
+        units: None,
+        description: "Number of online devices.",
+        value: |s| devices(s, 0),
+    },
+    Object {
+        sub_id: 31,
+        name: "hwmOfflineDevices",
+        syntax: Syntax::Gauge32,
+        units: None,
+        description: "Number of offline devices.",
+        value: |s| devices(s, 1),
+    },
+    Object {
+        sub_id: 32,
+        name: "hwmTotalDevices",
+        syntax: Syntax::Gauge32,
+        units: None,
+        description: "Number of devices known to the router.",
+        value: |s| devices(s, 2),
+    },
+    Object {
+        sub_id: 33,
+        name: "hwmWifiDevices",
+        syntax: Syntax::Gauge32,
+        units: None,
+        description: "Number of wifi devices.",
+        value: |s| devices(s, 3),
+    },
+    Object {
+        sub_id: 34,
+        name: "hwmWifi2GHzDevices",
+        syntax: Syntax::Gauge32,
+        units: None,
+        description: "Number of 2.4 GHz wifi devices.",
+        value: |s| devices(s, 4),
+    },
+    Object {
+        sub_id: 35,
+        name: "hwmWifi5GHzDevices",
+        syntax: Syntax::Gauge32,
+        units: None,
+        description: "Number of 5 GHz wifi devices.",
+        value: |s| devices(s, 5),
+    },
+];
+
+/// Full instance OID of every object with a value in `snapshot`.
+pub fn values(snapshot: &Snapshot) -> BTreeMap<Vec<u32>, Value> {
+    OBJECTS
+        .iter()
+        .filter_map(|object| {
+            let mut oid = BASE_OID.to_vec();
+            oid.extend_from_slice(&[1, object.sub_id, 0]);
+            Some((oid, (object.value)(snapshot)?))
+        })
+        .collect()
+}
+
+/// The MIB module describing `OBJECTS`.
+pub fn mib() -> String {
+    let mut mib = format!(
+        r#"{name} DEFINITIONS ::= BEGIN
+
+IMPORTS
+    MODULE-IDENTITY, OBJECT-TYPE, Integer32, Gauge32, Counter32, Counter64, enterprises
+        FROM SNMPv2-SMI
+    DisplayString
+        FROM SNMPv2-TC;
+
+huaweiMetrics MODULE-IDENTITY
+    LAST-UPDATED "202610190000Z"
+    ORGANIZATION "huawei-metrics-exporter"
+    CONTACT-INFO "https://github.com/mtib/huawei-metrics-exporter"
+    DESCRIPTION  "Values scraped from the web interface of HUAWEI LTE routers."
+    ::= {{ enterprises {pen} }}
+
+hwmObjects OBJECT IDENTIFIER ::= {{ huaweiMetrics 1 }}
+"#,
+        name = MIB_NAME,
+        pen = BASE_OID[6]
+    );
+    for object in OBJECTS {
+        let syntax = match object.syntax {
+            Syntax::DisplayString => "DisplayString",
+            Syntax::Integer32 => "Integer32",
+            Syntax::Gauge32 => "Gauge32",
+            Syntax::Counter32 => "Counter32",
+            Syntax::Counter64 => "Counter64",
+        };
+        write!(
+            mib,
+            "\n{} OBJECT-TYPE\n    SYNTAX      {}\n",
+            object.name, syntax
+        )
+        .unwrap();
+        if let Some(units) = object.units {
+            writeln!(mib, "    UNITS       \"{}\"", units).unwrap();
+        }
+        write!(
+            mib,
+            "    MAX-ACCESS  read-only\n    STATUS      current\n    DESCRIPTION \"{}\"\n    ::= {{ hwmObjects {} }}\n",
+            object.description, object.sub_id
+        )
+        .unwrap();
+    }
+    mib.push_str("\nEND\n");
+    mib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mib, values, Value, BASE_OID};
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn shipped_mib_is_up_to_date() {
+        assert_eq!(include_str!("../mibs/HUAWEI-METRICS-MIB.txt"), mib());
+
+        let values = values(&Snapshot::example());
+        let oid = |sub_id| [&BASE_OID[..], &[1, sub_id, 0]].concat();
+        assert_eq!(values[&oid(13)], Value::Integer(-10));
+        assert_eq!(values[&oid(20)], Value::Counter64(2048 * 1024 * 1024));
+        assert!(!values.contains_key(&oid(11)));
+    }
+}
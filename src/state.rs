@@ -0,0 +1,38 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{counters::CounterTracker, events::Tracker, quota::QuotaEnforcer, usage::UsageLedger};
+
+/// Everything the exporter remembers between two runs, persisted as JSON.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct State {
+    #[serde(default)]
+    pub usage: UsageLedger,
+    #[serde(default)]
+    pub counters: CounterTracker,
+    #[serde(default)]
+    pub quota: QuotaEnforcer,
+    #[serde(default)]
+    pub events: Tracker,
+}
+
+impl State {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).expect("State file is not valid JSON"),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("No state file at {}, starting fresh", path.display());
+                State::default()
+            }
+            Err(e) => panic!("Unable to read state file {}: {:#}", path.display(), e),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        trace!("Writing state to {}", path.display());
+        fs::write(path, serde_json::to_string_pretty(self).unwrap()).unwrap();
+    }
+}
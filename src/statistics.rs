@@ -0,0 +1,96 @@
+use fantoccini::{elements::Element, Locator};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{parse_value, MinuteCounter, Parsed};
+
+/// The router's own traffic statistics and data plan settings from its statistics page.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Statistics {
+    pub month_download: Option<Parsed>,
+    pub month_upload: Option<Parsed>,
+    pub month_duration_seconds: Option<u64>,
+    pub plan: DataPlan,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataPlan {
+    pub start_day: Option<u32>,
+    pub limit: Option<Parsed>,
+    pub warning_threshold_percent: Option<f64>,
+}
+
+async fn value_of(page: &mut Element, id: &str) -> Option<String> {
+    match page.find(Locator::Id(id)).await {
+        Ok(mut el) => {
+            let text = el.text().await.unwrap().trim().to_owned();
+            trace!("Statistics value {}: {}", id, text);
+            Some(text)
+        }
+        Err(e) => {
+            warn!("Unable to find #{} on statistics page: {:#}", id, e);
+            None
+        }
+    }
+}
+
+fn leading_number<T: std::str::FromStr>(text: &str) -> Option<T> {
+    let re = Regex::new(r"\d+(\.\d+)?").expect("Regex compilation failed");
+    re.find(text)?.as_str().parse().ok()
+}
+
+/// Parses durations like `12:34:56`, `3 day 12:34:56` or `3 day 12 hour 34 minute`.
+fn parse_duration(text: &str) -> Option<u64> {
+    let re =
+        Regex::new(r"^(?:(?P<day>\d+) days? )?(?P<hour>\d+):(?P<minute>\d{2}):(?P<second>\d{2})$")
+            .expect("Regex compilation failed");
+    if let Some(cap) = re.captures(text) {
+        let part = |name| {
+            cap.name(name)
+                .map_or(Some(0), |m| m.as_str().parse::<u64>().ok())
+        };
+        Some(
+            part("day")? * 24 * 3600
+                + part("hour")? * 3600
+                + part("minute")? * 60
+                + part("second")?,
+        )
+    } else {
+        MinuteCounter::try_from_str(text, false).map(|c| c.minutes * 60)
+    }
+}
+
+pub async fn extract_statistics(page: &mut Element) -> Statistics {
+    let month_download = value_of(page, "statistic_current_month_download").await;
+    let month_upload = value_of(page, "statistic_current_month_upload").await;
+    let month_duration = value_of(page, "statistic_current_month_duration").await;
+    let start_day = value_of(page, "statistic_start_date").await;
+    let limit = value_of(page, "statistic_data_limit").await;
+    let threshold = value_of(page, "statistic_threshold").await;
+
+    let statistics = Statistics {
+        month_download: month_download.as_deref().and_then(parse_value),
+        month_upload: month_upload.as_deref().and_then(parse_value),
+        month_duration_seconds: month_duration.as_deref().and_then(parse_duration),
+        plan: DataPlan {
+            start_day: start_day.as_deref().and_then(leading_number),
+            limit: limit.as_deref().and_then(parse_value),
+            warning_threshold_percent: threshold.as_deref().and_then(leading_number),
+        },
+    };
+    trace!("Found statistics: {:?}", statistics);
+    statistics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_duration;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("01:02:03"), Some(3723));
+        assert_eq!(parse_duration("2 days 00:00:10"), Some(2 * 24 * 3600 + 10));
+        assert_eq!(parse_duration("1 day 1 hour 1 minute"), Some(90060));
+        assert_eq!(parse_duration("unknown"), None);
+    }
+}
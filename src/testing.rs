@@ -0,0 +1,9 @@
+use tempfile::TempDir;
+
+/// A fresh directory for a test, removed when dropped even if the test fails.
+pub fn temp_dir(name: &str) -> TempDir {
+    tempfile::Builder::new()
+        .prefix(&format!("huawei-metrics-{}-", name))
+        .tempdir()
+        .expect("Unable to create temporary directory")
+}
@@ -0,0 +1,324 @@
+use std::{collections::BTreeMap, fmt::Write, ops::AddAssign};
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+pub const BYTES_PER_GB: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct Traffic {
+    pub download: u64,
+    pub upload: u64,
+}
+
+impl Traffic {
+    pub fn total(&self) -> u64 {
+        self.download + self.upload
+    }
+}
+
+impl AddAssign for Traffic {
+    fn add_assign(&mut self, other: Self) {
+        self.download += other.download;
+        self.upload += other.upload;
+    }
+}
+
+/// Raw traffic counters as reported by the router at a point in time.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Reading {
+    pub time: DateTime<Local>,
+    pub traffic: Traffic,
+}
+
+/// Traffic accounted per local calendar day, built from consecutive counter readings.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct UsageLedger {
+    last: Option<Reading>,
+    daily: BTreeMap<NaiveDate, Traffic>,
+}
+
+impl UsageLedger {
+    pub fn last(&self) -> Option<&Reading> {
+        self.last.as_ref()
+    }
+
+    /// Accounts the traffic since the previous reading to the day of `reading`.
+    pub fn record(&mut self, reading: Reading) {
+        if let Some(last) = self.last {
+            let delta = Traffic {
+                download: counter_delta(last.traffic.download, reading.traffic.download),
+                upload: counter_delta(last.traffic.upload, reading.traffic.upload),
+            };
+            trace!("Traffic since last reading: {:?}", delta);
+            *self
+                .daily
+                .entry(reading.time.naive_local().date())
+                .or_default() += delta;
+        } else {
+            info!("First traffic counter reading, usage accounting starts now");
+        }
+        self.last = Some(reading);
+    }
+
+    /// Traffic accounted on days in `from..to`.
+    pub fn between(&self, from: NaiveDate, to: NaiveDate) -> Traffic {
+        let mut sum = Traffic::default();
+        for (_, traffic) in self.daily.range(from..to) {
+            sum += *traffic;
+        }
+        sum
+    }
+
+    /// Traffic of every day with a reading, oldest first.
+    pub fn days(&self) -> impl Iterator<Item = (&NaiveDate, &Traffic)> {
+        self.daily.iter()
+    }
+
+    pub fn day(&self, date: NaiveDate) -> Traffic {
+        self.between(date, date.succ_opt().unwrap())
+    }
+
+    pub fn month(&self, date: NaiveDate) -> Traffic {
+        let first = first_of_month(date);
+        self.between(first, add_months(first, 1))
+    }
+}
+
+/// Counters are expected to only grow, a smaller value means the router has reset them and
+/// everything counted since then is new traffic.
+fn counter_delta(previous: u64, current: u64) -> u64 {
+    if current >= previous {
+        current - previous
+    } else {
+        warn!(
+            "Traffic counter went from {} to {}, assuming it was reset",
+            previous, current
+        );
+        current
+    }
+}
+
+/// Average throughput between two readings, in the router's unit of Mbps.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AverageRates {
+    pub since: DateTime<Local>,
+    pub seconds: f64,
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+}
+
+impl AverageRates {
+    pub fn between(previous: &Reading, current: &Reading) -> Option<Self> {
+        let seconds = (current.time - previous.time).num_milliseconds() as f64 / 1000f64;
+        if seconds <= 0f64 {
+            warn!("Previous reading is not older than the current one, skipping rates");
+            return None;
+        }
+        let mbps = |previous: u64, current: u64| {
+            current.saturating_sub(previous) as f64 * 8f64 / (1024f64 * 1024f64) / seconds
+        };
+        Some(AverageRates {
+            since: previous.time,
+            seconds,
+            download_mbps: mbps(previous.traffic.download, current.traffic.download),
+            upload_mbps: mbps(previous.traffic.upload, current.traffic.upload),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BillingCycle {
+    /// Day of month on which a new cycle starts, clamped to the last day of shorter months.
+    pub start_day: u32,
+    pub quota: Option<u64>,
+}
+
+impl BillingCycle {
+    /// Start (inclusive) and end (exclusive) of the cycle containing `today`.
+    pub fn bounds(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let this_month = cycle_start(today, self.start_day);
+        let start = if today >= this_month {
+            this_month
+        } else {
+            cycle_start(add_months(first_of_month(today), -1), self.start_day)
+        };
+        (
+            start,
+            cycle_start(add_months(first_of_month(start), 1), self.start_day),
+        )
+    }
+
+    pub fn usage(&self, ledger: &UsageLedger, now: DateTime<Local>) -> CycleUsage {
+        let (start, end) = self.bounds(now.naive_local().date());
+        let used = ledger.between(start, end);
+
+        let elapsed =
+            (now.naive_local() - start.and_hms_opt(0, 0, 0).unwrap()).max(Duration::hours(1));
+        let length = end - start;
+        let projected = (used.total() as f64 * length.num_seconds() as f64
+            / elapsed.num_seconds() as f64) as u64;
+
+        CycleUsage {
+            start,
+            end,
+            used,
+            quota: self.quota,
+            remaining: self.quota.map(|q| q.saturating_sub(used.total())),
+            projected,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CycleUsage {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub used: Traffic,
+    pub quota: Option<u64>,
+    pub remaining: Option<u64>,
+    /// Total traffic at the end of the cycle if usage continues at the average rate so far.
+    pub projected: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageSummary {
+    pub today: Traffic,
+    pub month: Traffic,
+    pub cycle: CycleUsage,
+}
+
+impl UsageSummary {
+    pub fn new(ledger: &UsageLedger, cycle: &BillingCycle, now: DateTime<Local>) -> Self {
+        let today = now.naive_local().date();
+        UsageSummary {
+            today: ledger.day(today),
+            month: ledger.month(today),
+            cycle: cycle.usage(ledger, now),
+        }
+    }
+}
+
+pub fn first_of_month(date: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap()
+}
+
+fn add_months(first: NaiveDate, months: i32) -> NaiveDate {
+    let index = first.year() * 12 + first.month0() as i32 + months;
+    NaiveDate::from_ymd_opt(index.div_euclid(12), index.rem_euclid(12) as u32 + 1, 1).unwrap()
+}
+
+fn cycle_start(month: NaiveDate, start_day: u32) -> NaiveDate {
+    let first = first_of_month(month);
+    let days_in_month = add_months(first, 1).pred_opt().unwrap().day();
+    NaiveDate::from_ymd_opt(first.year(), first.month(), start_day.min(days_in_month)).unwrap()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    format!("{:.2} GB", bytes as f64 / BYTES_PER_GB as f64)
+}
+
+/// Human readable overview of the last `days` days, the current month and billing cycle.
+pub fn usage_table(
+    ledger: &UsageLedger,
+    cycle: &BillingCycle,
+    now: DateTime<Local>,
+    days: u32,
+) -> String {
+    let today = now.naive_local().date();
+    let usage = cycle.usage(ledger, now);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:<30}{:>14}{:>14}{:>14}",
+        "Period", "Download", "Upload", "Total"
+    )
+    .unwrap();
+    let mut row = |period: &str, traffic: Traffic| {
+        writeln!(
+            out,
+            "{:<30}{:>14}{:>14}{:>14}",
+            period,
+            format_bytes(traffic.download),
+            format_bytes(traffic.upload),
+            format_bytes(traffic.total())
+        )
+        .unwrap();
+    };
+
+    for offset in (0..days).rev() {
+        let date = today - Duration::days(offset as i64);
+        row(&date.to_string(), ledger.day(date));
+    }
+    row(
+        &today.format("Month %Y-%m").to_string(),
+        ledger.month(today),
+    );
+    row(
+        &format!("Cycle {} - {}", usage.start, usage.end.pred_opt().unwrap()),
+        usage.used,
+    );
+
+    writeln!(out).unwrap();
+    if let Some(quota) = usage.quota {
+        writeln!(out, "Quota:     {}", format_bytes(quota)).unwrap();
+        writeln!(
+            out,
+            "Remaining: {}",
+            format_bytes(usage.remaining.unwrap_or(0))
+        )
+        .unwrap();
+    }
+    writeln!(out, "Projected: {}", format_bytes(usage.projected)).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    use super::{BillingCycle, Reading, Traffic, UsageLedger};
+
+    #[test]
+    fn counter_reset() {
+        let mut ledger = UsageLedger::default();
+        for (hour, download) in [(0, 100), (1, 150), (2, 20), (3, 50)] {
+            ledger.record(Reading {
+                time: Local.with_ymd_and_hms(2021, 6, 1, hour, 0, 0).unwrap(),
+                traffic: Traffic {
+                    download,
+                    upload: 0,
+                },
+            });
+        }
+        assert_eq!(
+            ledger
+                .day(NaiveDate::from_ymd_opt(2021, 6, 1).unwrap())
+                .download,
+            100
+        );
+    }
+
+    #[test]
+    fn cycle_bounds() {
+        let cycle = BillingCycle {
+            start_day: 31,
+            quota: None,
+        };
+        assert_eq!(
+            cycle.bounds(NaiveDate::from_ymd_opt(2021, 3, 15).unwrap()),
+            (
+                NaiveDate::from_ymd_opt(2021, 2, 28).unwrap(),
+                NaiveDate::from_ymd_opt(2021, 3, 31).unwrap()
+            )
+        );
+        assert_eq!(
+            cycle.bounds(NaiveDate::from_ymd_opt(2021, 12, 31).unwrap()),
+            (
+                NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
+                NaiveDate::from_ymd_opt(2022, 1, 31).unwrap()
+            )
+        );
+    }
+}